@@ -1,12 +1,113 @@
 use clap::{builder::PossibleValue, Parser, ValueEnum};
-use std::path::PathBuf;
+use std::{fmt::Display, path::PathBuf};
 
-use crate::bgrid::Charset;
+use gol_rs::{
+    bgrid::Charset,
+    cycle::OnCycle,
+    hashlife::MAX_STEP,
+    rule::AnyRule,
+    soup::{Size, Symmetry},
+    topology::Grid,
+};
+
+/// Terminal library the interface draws with
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Backend {
+    Curses,
+    Crossterm,
+}
+
+/// Curses when it was built in, as it's the one the interface started on
+impl Default for Backend {
+    fn default() -> Self {
+        if cfg!(feature = "curses") || !cfg!(feature = "crossterm") {
+            Backend::Curses
+        } else {
+            Backend::Crossterm
+        }
+    }
+}
+
+impl Display for Backend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Backend::Curses => write!(f, "curses"),
+            Backend::Crossterm => write!(f, "crossterm"),
+        }
+    }
+}
+
+impl ValueEnum for Backend {
+    fn value_variants<'a>() -> &'a [Self] {
+        &[Backend::Curses, Backend::Crossterm]
+    }
+
+    fn to_possible_value(&self) -> Option<PossibleValue> {
+        Some(match self {
+            Backend::Curses => PossibleValue::new("curses"),
+            Backend::Crossterm => PossibleValue::new("crossterm"),
+        })
+    }
+}
 
 #[derive(Parser, Debug)]
 pub struct Args {
-    #[arg(help = "pgm file to read initial state from")]
-    pub input: PathBuf,
+    #[arg(
+        required_unless_present = "random",
+        conflicts_with = "random",
+        help = "PGM (P2/P5), PBM (P1/P4), RLE, plaintext (.cells), Life 1.05/1.06 (.lif) or macrocell (.mc) file to read initial state from, with --hashlife macrocell patterns needn't fit on a board"
+    )]
+    pub input: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "WxH",
+        conflicts_with = "random",
+        help = "pad the input pattern out to a board of this size, centred on it, or the window onto a macrocell pattern run with --hashlife"
+    )]
+    pub size: Option<Size>,
+
+    #[arg(
+        long,
+        default_value_t = 1.0,
+        value_name = "LEVEL",
+        conflicts_with = "random",
+        help = "fraction of the max grey a pixel of a PGM input has to reach to be alive, 1 only takes full white"
+    )]
+    pub threshold: f64,
+
+    #[arg(
+        long,
+        help = "read dark pixels of a PGM input as alive instead of light ones"
+    )]
+    pub invert: bool,
+
+    #[arg(
+        long,
+        value_name = "WxH",
+        help = "start from a random soup of this size"
+    )]
+    pub random: Option<Size>,
+
+    #[arg(
+        long,
+        default_value_t = 0.5,
+        help = "chance of each cell of a random soup being alive"
+    )]
+    pub density: f64,
+
+    #[arg(
+        long,
+        help = "seed for the random soup, printed on exit when left out so the soup can be made again"
+    )]
+    pub seed: Option<u64>,
+
+    #[arg(
+        long,
+        default_value_t = Symmetry::default(),
+        help = "symmetry of the random soup as named by apgsearch, e.g. C1, C2_4, C4_4, D2_+1 or D8_1"
+    )]
+    pub symmetry: Symmetry,
 
     #[arg(short, long, help = "threads to use")]
     pub threads: Option<u16>,
@@ -18,26 +119,121 @@ pub struct Args {
     )]
     pub background: char,
 
-    #[arg(value_enum, long, default_value_t = Charset::Braille, help = "unicode character set to use for rendering")]
-    pub charset: Charset,
-}
+    #[arg(
+        long,
+        help = "rule in B/S notation (e.g. B36/S23), S/B notation (e.g. 23/36), Larger than Life notation (e.g. R5,C0,M1,S34..58,B34..45,NM) or Wireworld, defaults to the input file's rule or Life"
+    )]
+    pub rule: Option<AnyRule>,
 
-impl ValueEnum for Charset {
-    fn value_variants<'a>() -> &'a [Self] {
-        &[
-            Charset::Block,
-            Charset::Braille,
-            Charset::None,
-            Charset::Ascii,
-        ]
-    }
+    #[arg(
+        long,
+        default_value_t = Grid::default(),
+        conflicts_with_all = ["hashlife", "unbounded"],
+        help = "how the board's edges are joined: plane, torus, klein, cross or sphere, or a Golly bounded grid such as :T100,80 or :K100*,80 which also sets the board size"
+    )]
+    pub topology: Grid,
 
-    fn to_possible_value(&self) -> Option<clap::builder::PossibleValue> {
-        Some(match self {
-            Charset::Braille => PossibleValue::new("braille"),
-            Charset::Block => PossibleValue::new("block"),
-            Charset::None => PossibleValue::new("none"),
-            Charset::Ascii => PossibleValue::new("ascii"),
-        })
-    }
+    #[arg(
+        long,
+        help = "run with the hashlife engine, the plane is unbounded rather than wrapping around the board"
+    )]
+    pub hashlife: bool,
+
+    #[arg(
+        long,
+        conflicts_with = "hashlife",
+        help = "run on an unbounded plane which grows with the pattern instead of wrapping around the board"
+    )]
+    pub unbounded: bool,
+
+    #[arg(
+        long,
+        default_value_t = 0,
+        value_parser = clap::value_parser!(u8).range(..=MAX_STEP as i64),
+        help = "generations per hashlife step as a power of two, change it while running with +/-"
+    )]
+    pub step: u8,
+
+    #[arg(
+        value_enum,
+        long,
+        default_value_t = OnCycle::Continue,
+        help = "what to do once the board becomes periodic, the period and where it starts are shown either way, space resumes a paused run"
+    )]
+    pub on_cycle: OnCycle,
+
+    #[arg(
+        long,
+        default_value_t = 1024,
+        help = "generations remembered when looking for cycles, longer periods are not detected"
+    )]
+    pub history: usize,
+
+    #[arg(
+        long,
+        default_value_t = 64,
+        value_name = "MIB",
+        help = "memory for past boards which can be stepped back through with , and ."
+    )]
+    pub undo_memory: usize,
+
+    #[arg(
+        long,
+        help = "print a census of the objects on the board on exit, press c while running to show one"
+    )]
+    pub census: bool,
+
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "write population, births, deaths, bounding box and step time of every generation, as NDJSON for .ndjson or .jsonl files and CSV otherwise"
+    )]
+    pub stats_out: Option<PathBuf>,
+
+    #[arg(
+        long,
+        help = "run without the terminal interface until --generations have passed or --on-cycle stops it"
+    )]
+    pub headless: bool,
+
+    #[arg(
+        long,
+        value_name = "N",
+        requires = "headless",
+        help = "generations to run for in headless mode"
+    )]
+    pub generations: Option<u64>,
+
+    #[arg(
+        long,
+        requires = "headless",
+        help = "print the generation, population, bounding box and time taken once a headless run ends"
+    )]
+    pub summary: bool,
+
+    #[arg(
+        short,
+        long,
+        value_name = "FILE",
+        help = "write the final board on exit, in the format named by the extension (.pgm, .pbm, .rle, .cells, .lif or .mc), a hashlife run is saved whole as .mc"
+    )]
+    pub output: Option<PathBuf>,
+
+    #[arg(
+        long,
+        requires = "output",
+        help = "write .pbm output as plain P1 text rather than packed P4"
+    )]
+    pub plain: bool,
+
+    #[arg(
+        value_enum,
+        long,
+        default_value_t = Backend::default(),
+        help = "terminal library to draw with, crossterm needs no native libraries and adds true color and mouse input"
+    )]
+    pub backend: Backend,
+
+    #[arg(value_enum, long, default_value_t = Charset::Braille, help = "unicode character set to use for rendering")]
+    pub charset: Charset,
 }
@@ -1,3 +1,4 @@
+use clap::{builder::PossibleValue, ValueEnum};
 use std::ops::{Deref, DerefMut};
 
 use crate::gol::{Board, Mask, Point};
@@ -5,6 +6,81 @@ use crate::gol::{Board, Mask, Point};
 pub struct Frame {
     pts: Board,
     view: Mask,
+    hex: bool,
+    palette: Palette,
+}
+
+/// What a rendered glyph shows, lets the frontend color cells by their
+/// state and the board border apart from them
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Shade {
+    Cell(Color),
+    Border,
+}
+
+/// Foreground colors cells can be drawn in
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Color {
+    White,
+    Red,
+    Green,
+    Yellow,
+    Blue,
+    Magenta,
+    Cyan,
+}
+
+/// How cells in one state are drawn, the glyph is only used by charsets
+/// showing a single cell per character
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Paint {
+    pub glyph: char,
+    pub color: Color,
+}
+
+/// Paint for every state of a rule, indexed by state code. The empty state
+/// (0) is never painted
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Palette(Vec<Paint>);
+
+impl Palette {
+    pub fn new(paints: Vec<Paint>) -> Self {
+        Self(paints)
+    }
+    /// Live cells in white and dying ones in red, for Life-like and
+    /// Generations rules
+    pub fn generations(states: u8) -> Self {
+        let paint = |state| match state {
+            1 => Paint {
+                glyph: 'A',
+                color: Color::White,
+            },
+            _ => Paint {
+                glyph: 'a',
+                color: Color::Red,
+            },
+        };
+        // always has a dying paint so boards read with the wrong rule
+        // still draw their dying cells apart
+        Self((0..states.max(3)).map(paint).collect())
+    }
+    /// Paint of cells in `state`, states past the end share the last one
+    pub fn paint(&self, state: u8) -> Paint {
+        let last = self.0.len().saturating_sub(1);
+        self.0
+            .get((state as usize).min(last))
+            .copied()
+            .unwrap_or(Paint {
+                glyph: 'A',
+                color: Color::White,
+            })
+    }
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Self::generations(2)
+    }
 }
 
 #[derive(Copy, Clone, Debug)]
@@ -16,9 +92,9 @@ pub enum Charset {
 }
 
 impl Charset {
-    fn calc_braille_offset(self, alive: &Vec<bool>) -> u8 {
+    fn calc_braille_offset(self, alive: &[bool]) -> u8 {
         let (w, h) = self.scale();
-        transpose(&alive, w, h)
+        transpose(alive, w, h)
             .into_iter()
             .enumerate()
             .fold(0, |xs, (n, x)| {
@@ -34,15 +110,15 @@ impl Charset {
             Charset::Ascii => (1, 1),
         }
     }
-    pub fn encode(self, bg: char, alive: &Vec<bool>) -> char {
+    pub fn encode(self, bg: char, alive: &[bool]) -> char {
         assert_eq!(alive.len() as u32, self.scale().0 * self.scale().1);
         match self {
             Charset::Braille => {
-                let off = self.calc_braille_offset(&alive) as u32;
+                let off = self.calc_braille_offset(alive) as u32;
                 if off == 0 {
                     bg
                 } else {
-                    char::from_u32(0x2800 as u32 + off).unwrap()
+                    char::from_u32(0x2800_u32 + off).unwrap()
                 }
             }
             Charset::Block => {
@@ -98,13 +174,12 @@ impl Charset {
     }
 }
 
-fn transpose<T>(v: &Vec<T>, w: u32, h: u32) -> Vec<T>
+fn transpose<T>(v: &[T], w: u32, h: u32) -> Vec<T>
 where
     T: Clone,
 {
     assert_eq!(v.len() as u32, w * h);
-    let mut out = Vec::new();
-    out.reserve(v.len());
+    let mut out = Vec::with_capacity(v.len());
 
     for x in 0..w {
         for y in 0..h {
@@ -116,13 +191,30 @@ where
     out
 }
 
-type Rendered = Vec<(Point, char)>;
+type Rendered = Vec<(Point, char, Shade)>;
 
 impl Frame {
     pub fn new(pts: Board, view: Mask) -> Self {
-        Self { pts, view }
+        Self {
+            pts,
+            view,
+            hex: false,
+            palette: Palette::default(),
+        }
+    }
+    /// Draw states with `palette` in place of the default live and dying
+    /// colors
+    pub fn with_palette(mut self, palette: Palette) -> Self {
+        self.palette = palette;
+        self
+    }
+    /// Render for a hexagonal neighborhood: every cell is drawn two
+    /// sub-pixels wide and each row is shifted half a cell left of the one
+    /// above it, so the six neighbors of a cell surround it on screen
+    pub fn hex_skewed(mut self, hex: bool) -> Self {
+        self.hex = hex;
+        self
     }
-    #[allow(dead_code)]
     pub fn render_box(&self) -> Rendered {
         self.render(' ', Charset::Block)
     }
@@ -130,7 +222,8 @@ impl Frame {
     pub fn render(&self, background: char, charset: Charset) -> Rendered {
         let (scalex, scaley) = charset.scale();
         let maxh = self.view.h.min(self.pts.height() / scaley);
-        let maxw = self.view.w.min(self.pts.width() / scalex);
+        let stretch = if self.hex { 2 } else { 1 };
+        let maxw = self.view.w.min(self.pts.width() * stretch / scalex);
         let offset = Point {
             x: (self.view.w.abs_diff(maxw) / 2) as i64,
             y: (self.view.h.abs_diff(maxh) / 2) as i64,
@@ -151,7 +244,7 @@ impl Frame {
                 let bounds = &bounds;
                 let charset = &charset;
                 (offset.x as u32..maxw).map(move |x| {
-                    let alive = (0..scaley)
+                    let states = (0..scaley)
                         .flat_map(|oy| {
                             (0..scalex).map(move |ox| Point {
                                 x: (x * scalex + ox + self.view.x) as i64,
@@ -160,40 +253,56 @@ impl Frame {
                         })
                         .map(|p| {
                             if !bounds.contains(&p) {
-                                false
+                                0
+                            } else if self.hex {
+                                self.pts.state(Point {
+                                    x: (p.x + p.y).div_euclid(2),
+                                    y: p.y,
+                                })
                             } else {
-                                self.pts[p]
+                                self.pts.state(p)
                             }
                         })
                         .collect::<Vec<_>>();
-                    let ch = charset.encode(background, &alive);
+                    let lit = states.iter().map(|s| *s != 0).collect::<Vec<_>>();
+                    // a glyph covering several states takes the lowest one's
+                    // paint, so live cells win over dying ones
+                    let paint = states
+                        .iter()
+                        .filter(|s| **s != 0)
+                        .min()
+                        .map(|s| self.palette.paint(*s));
+                    let ch = match (charset, paint) {
+                        (Charset::Ascii, Some(p)) => p.glyph,
+                        _ => charset.encode(background, &lit),
+                    };
+                    let shade = Shade::Cell(paint.map_or(Color::White, |p| p.color));
                     (
                         Point {
                             x: x as i64,
                             y: y as i64,
                         },
                         ch,
+                        shade,
                     )
                 })
             })
             .collect();
         if maxw < self.view.w {
-            frame
-                .extend((offset.y as u32 + 1..maxh).flat_map(|y| {
-                    [offset.x, maxw as i64].map(|x| (Point { x, y: y as i64 }, '│'))
-                }));
+            frame.extend((offset.y as u32 + 1..maxh).flat_map(|y| {
+                [offset.x, maxw as i64].map(|x| (Point { x, y: y as i64 }, '│', Shade::Border))
+            }));
         }
         if maxh < self.view.h {
-            frame
-                .extend((offset.x as u32 + 1..maxw).flat_map(|x| {
-                    [offset.y, maxh as i64].map(|y| (Point { x: x as i64, y }, '─'))
-                }));
+            frame.extend((offset.x as u32 + 1..maxw).flat_map(|x| {
+                [offset.y, maxh as i64].map(|y| (Point { x: x as i64, y }, '─', Shade::Border))
+            }));
         }
         if maxh < self.view.h && maxw < self.view.w {
-            frame.push(((offset.x, offset.y).into(), '┌'));
-            frame.push(((offset.x, maxh).into(), '└'));
-            frame.push(((maxw, offset.y).into(), '┐'));
-            frame.push(((maxw, maxh).into(), '┘'));
+            frame.push(((offset.x, offset.y).into(), '┌', Shade::Border));
+            frame.push(((offset.x, maxh).into(), '└', Shade::Border));
+            frame.push(((maxw, offset.y).into(), '┐', Shade::Border));
+            frame.push(((maxw, maxh).into(), '┘', Shade::Border));
         }
         frame
     }
@@ -212,6 +321,26 @@ impl DerefMut for Frame {
     }
 }
 
+impl ValueEnum for Charset {
+    fn value_variants<'a>() -> &'a [Self] {
+        &[
+            Charset::Block,
+            Charset::Braille,
+            Charset::None,
+            Charset::Ascii,
+        ]
+    }
+
+    fn to_possible_value(&self) -> Option<clap::builder::PossibleValue> {
+        Some(match self {
+            Charset::Braille => PossibleValue::new("braille"),
+            Charset::Block => PossibleValue::new("block"),
+            Charset::None => PossibleValue::new("none"),
+            Charset::Ascii => PossibleValue::new("ascii"),
+        })
+    }
+}
+
 #[cfg(test)]
 mod test {
     use itertools::Itertools;
@@ -235,21 +364,22 @@ mod test {
     #[test]
     fn test_individual() {
         let mut f = empty_frame();
-        f[Point { x: 0, y: 0 }] = true;
+        f.set(Point { x: 0, y: 0 }, true);
         assert_eq!(f.render_box()[0].1, '▘');
 
-        f[Point { x: 1, y: 1 }] = true;
+        f.set(Point { x: 1, y: 1 }, true);
         assert_eq!(f.render_box()[0].1, '▚');
 
-        f[Point { x: 1, y: 0 }] = true;
+        f.set(Point { x: 1, y: 0 }, true);
         assert_eq!(f.render_box()[0].1, '▜');
-        f[Point { x: 1, y: 0 }] = false;
+        f.set(Point { x: 1, y: 0 }, false);
 
-        f[Point { x: 0, y: 1 }] = true;
+        f.set(Point { x: 0, y: 1 }, true);
         assert_eq!(f.render_box()[0].1, '▙');
-        f[Point { x: 0, y: 1 }] = false;
+        f.set(Point { x: 0, y: 1 }, false);
     }
     #[test]
+    #[allow(clippy::len_zero)]
     fn test_all_defined() {
         for pts in (0..2)
             .flat_map(|y| {
@@ -262,7 +392,7 @@ mod test {
         {
             let mut f = empty_frame();
             for pt in &pts {
-                f[pt.clone()] = true;
+                f.set(pt.clone(), true);
             }
             if pts.len() > 0 {
                 assert_ne!(
@@ -279,16 +409,16 @@ mod test {
     #[test]
     fn test_bot_right() {
         let mut f = empty_frame();
-        f[Point { x: 0, y: 1 }] = true;
-        f[Point { x: 1, y: 0 }] = true;
-        f[Point { x: 1, y: 1 }] = true;
+        f.set(Point { x: 0, y: 1 }, true);
+        f.set(Point { x: 1, y: 0 }, true);
+        f.set(Point { x: 1, y: 1 }, true);
         assert_eq!(f.render_box()[0].1, '▟');
     }
     #[test]
     fn test_compress() {
         let mut f = empty_frame();
-        f[Point { x: 0, y: 0 }] = true;
-        f[Point { x: 2, y: 0 }] = true;
+        f.set(Point { x: 0, y: 0 }, true);
+        f.set(Point { x: 2, y: 0 }, true);
         let frame = f.render_box();
         assert_eq!(frame[0].0, Point { x: 0, y: 0 });
         assert_eq!(frame[1].0, Point { x: 1, y: 0 });
@@ -326,12 +456,84 @@ mod test {
     fn test_brailleset() {
         let mut f = empty_frame();
         let render = |f: &Frame| f.render(' ', Charset::Braille)[0].1;
-        f[Point { x: 0, y: 0 }] = true;
+        f.set(Point { x: 0, y: 0 }, true);
         assert_eq!(render(&f), '⠁');
-        f[Point { x: 0, y: 1 }] = true;
+        f.set(Point { x: 0, y: 1 }, true);
         assert_eq!(render(&f), '⠃');
     }
     #[test]
+    fn test_dying_shade() {
+        let mut f = empty_frame();
+        f.set_state(Point { x: 0, y: 0 }, 2);
+        let frame = f.render_box();
+        assert_eq!((frame[0].1, frame[0].2), ('▘', Shade::Cell(Color::Red)));
+        f.set_state(Point { x: 1, y: 1 }, 1);
+        assert_eq!(f.render_box()[0].2, Shade::Cell(Color::White));
+    }
+    #[test]
+    fn test_palette_glyphs() {
+        let mut f = empty_frame().with_palette(Palette::new(vec![
+            Paint {
+                glyph: ' ',
+                color: Color::White,
+            },
+            Paint {
+                glyph: '@',
+                color: Color::Blue,
+            },
+            Paint {
+                glyph: '#',
+                color: Color::Yellow,
+            },
+        ]));
+        f.set_state(Point { x: 0, y: 0 }, 2);
+        f.set_state(Point { x: 1, y: 0 }, 1);
+        f.set_state(Point { x: 0, y: 1 }, 7);
+        let frame = f.render('.', Charset::Ascii);
+        let cells = frame.iter().map(|(_, c, s)| (*c, *s)).collect_vec();
+        assert_eq!(
+            cells,
+            vec![
+                ('#', Shade::Cell(Color::Yellow)),
+                ('@', Shade::Cell(Color::Blue)),
+                ('#', Shade::Cell(Color::Yellow)),
+                ('.', Shade::Cell(Color::White)),
+            ]
+        );
+    }
+    #[test]
+    fn test_hex_skew() {
+        let mut board = Board::new(4, EMPTY.into());
+        board.set(Point { x: 1, y: 1 }, true);
+        board.set(Point { x: 1, y: 2 }, true);
+        let f = Frame::new(
+            board,
+            Mask {
+                x: 0,
+                y: 0,
+                w: 8,
+                h: 4,
+            },
+        )
+        .hex_skewed(true);
+        let lit = f
+            .render(' ', Charset::Ascii)
+            .into_iter()
+            .filter(|(_, c, _)| *c == 'A')
+            .map(|(p, _, _)| p)
+            .collect_vec();
+        // the southern neighbor sits half a cell to the left
+        assert_eq!(
+            lit,
+            vec![
+                Point { x: 1, y: 1 },
+                Point { x: 2, y: 1 },
+                Point { x: 0, y: 2 },
+                Point { x: 1, y: 2 }
+            ]
+        );
+    }
+    #[test]
     fn tranpose_swaps_w_and_h() {
         let initial = vec![0, 0, 1, 1, 2, 2];
         let transed = transpose(&initial, 2, 3);
@@ -0,0 +1,93 @@
+use anyhow::{bail, ensure, Result};
+use std::io::{Read, Write};
+
+use crate::{
+    gol::Board,
+    pattern::{Metadata, Pattern},
+};
+
+/// Read a plaintext (`.cells`) pattern, `O` cells are alive and `.` dead.
+/// Rows shorter than the widest are padded with dead cells
+pub fn read(f: &mut dyn Read) -> Result<Pattern> {
+    let mut text = String::new();
+    f.read_to_string(&mut text)?;
+    let mut meta = Metadata::default();
+    let mut rows = Vec::new();
+    for line in text.lines().map(|l| l.trim_end()) {
+        if let Some(comment) = line.strip_prefix('!') {
+            let comment = comment.trim();
+            if let Some(name) = comment.strip_prefix("Name:") {
+                meta.name = Some(name.trim().to_string());
+            } else if let Some(author) = comment.strip_prefix("Author:") {
+                meta.author = Some(author.trim().to_string());
+            } else {
+                meta.comments.push(comment.to_string());
+            }
+            continue;
+        }
+        let row = line
+            .chars()
+            .map(|c| match c {
+                '.' => Ok(false),
+                // some older files mark live cells with `*`
+                'O' | '*' => Ok(true),
+                c => bail!("invalid cell '{}'", c),
+            })
+            .collect::<Result<Vec<_>>>()?;
+        rows.push(row);
+    }
+    let width = rows.iter().map(Vec::len).max().unwrap_or(0);
+    ensure!(width > 0, "empty pattern");
+    let cells = rows
+        .into_iter()
+        .flat_map(|mut row| {
+            row.resize(width, false);
+            row
+        })
+        .collect();
+    Ok(Pattern {
+        board: Board::new(width as u32, cells),
+        meta,
+    })
+}
+
+/// Write `board` as a plaintext pattern, every row in full so the board
+/// keeps its size
+pub fn write(f: &mut dyn Write, board: &Board) -> Result<()> {
+    for row in board.states().chunks(board.width() as usize) {
+        let row: String = row
+            .iter()
+            .map(|s| if *s == 1 { 'O' } else { '.' })
+            .collect();
+        writeln!(f, "{}", row)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read() -> Result<()> {
+        let text =
+            "!Name: Glider\n!Author: Richard K. Guy\n!\n!The smallest spaceship\n.O\n..O\nOOO\n";
+        let pattern = read(&mut text.as_bytes())?;
+        assert_eq!(pattern.meta.name.as_deref(), Some("Glider"));
+        assert_eq!(pattern.meta.author.as_deref(), Some("Richard K. Guy"));
+        assert_eq!(pattern.meta.comments, ["", "The smallest spaceship"]);
+        assert_eq!(pattern.board.states(), [0, 1, 0, 0, 0, 1, 1, 1, 1]);
+        assert!(read(&mut "!Name: x\n".as_bytes()).is_err());
+        assert!(read(&mut ".O\nx.\n".as_bytes()).is_err());
+        Ok(())
+    }
+    #[test]
+    fn test_write_round_trip() -> Result<()> {
+        let board = Board::from_states(3, vec![0, 1, 0, 0, 0, 0]);
+        let mut out = Vec::new();
+        write(&mut out, &board)?;
+        assert_eq!(out, b".O.\n...\n");
+        assert_eq!(read(&mut out.as_slice())?.board.states(), board.states());
+        Ok(())
+    }
+}
@@ -0,0 +1,361 @@
+use anyhow::{ensure, Result};
+use std::{collections::BTreeMap, fmt::Display};
+
+use crate::{
+    gol::{Board, Point},
+    rule::LifeRule,
+    sparse::Plane,
+    topology::Topology,
+};
+
+/// Longest period looked for when classifying an island
+const MAX_PERIOD: u64 = 256;
+
+/// Name given to islands which don't settle into a still life, oscillator or
+/// spaceship within [`MAX_PERIOD`] generations when run on their own
+const UNKNOWN: &str = "zz_UNKNOWN";
+
+/// Column digits of the extended Wechsler format
+const DIGITS: &[u8; 32] = b"0123456789abcdefghijklmnopqrstuv";
+
+/// Common names of the objects most often left behind by Life soups
+const NAMES: &[(&str, &str)] = &[
+    ("xs4_33", "block"),
+    ("xp2_7", "blinker"),
+    ("xs6_696", "beehive"),
+    ("xq4_153", "glider"),
+    ("xs7_2596", "loaf"),
+    ("xs5_253", "boat"),
+    ("xs4_252", "tub"),
+    ("xs8_6996", "pond"),
+    ("xs6_356", "ship"),
+    ("xp2_7e", "toad"),
+    ("xp2_318c", "beacon"),
+    ("xq4_6frc", "lightweight spaceship"),
+];
+
+/// Tally of the objects on a board, keyed on their apgcode
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Census {
+    counts: BTreeMap<String, usize>,
+    /// Whether the rule is Life, so the common names apply
+    life: bool,
+}
+
+impl Census {
+    /// Split the live cells of `board` into islands and name each of them
+    pub fn take(board: &Board, rule: &LifeRule) -> Result<Self> {
+        Self::check(rule, board.topology())?;
+        let plane = Plane::new(rule)?;
+        let mut counts = BTreeMap::new();
+        for island in islands(board) {
+            *counts.entry(classify(&island, &plane)).or_insert(0) += 1;
+        }
+        Ok(Self {
+            counts,
+            life: *rule == LifeRule::life(),
+        })
+    }
+    /// Whether a census can be taken under `rule` on a board with
+    /// `topology`: islands are run on their own on an unbounded plane, and
+    /// can't be followed across twisted edges
+    pub fn check(rule: &LifeRule, topology: Topology) -> Result<()> {
+        ensure!(
+            rule.states() == 2 && rule.totalistic().is_some_and(|(birth, _)| birth & 1 == 0),
+            "the census needs a two-state totalistic rule without B0, got {}",
+            rule
+        );
+        ensure!(
+            matches!(topology, Topology::Plane | Topology::Torus),
+            "the census can't follow islands across twisted edges, it needs a plane or torus"
+        );
+        Ok(())
+    }
+    /// Objects with their counts, most common first
+    pub fn tally(&self) -> Vec<(&str, usize)> {
+        let mut tally: Vec<_> = self
+            .counts
+            .iter()
+            .map(|(code, n)| (code.as_str(), *n))
+            .collect();
+        tally.sort_by_key(|(code, n)| (std::cmp::Reverse(*n), *code));
+        tally
+    }
+    /// Common name of the object with apgcode `code`, if it has one
+    pub fn name(&self, code: &str) -> Option<&'static str> {
+        NAMES
+            .iter()
+            .find(|(c, _)| self.life && *c == code)
+            .map(|(_, name)| *name)
+    }
+    /// One line per object for the report and the overlay
+    pub fn lines(&self) -> Vec<String> {
+        self.tally()
+            .into_iter()
+            .map(|(code, n)| match self.name(code) {
+                Some(name) => format!("{:>6} {} ({})", n, code, name),
+                None => format!("{:>6} {}", n, code),
+            })
+            .collect()
+    }
+}
+
+impl Display for Census {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let total: usize = self.counts.values().sum();
+        writeln!(f, "{} objects", total)?;
+        for line in self.lines() {
+            writeln!(f, "{}", line)?;
+        }
+        Ok(())
+    }
+}
+
+/// Groups of live cells at most two cells apart, so that objects whose cells
+/// are not all touching (like the lightweight spaceship) stay in one piece.
+/// Coordinates are carried across joined edges so an island keeps its shape
+pub fn islands(board: &Board) -> Vec<Vec<Point>> {
+    let (w, h) = (board.width(), board.height());
+    let topology = board.topology();
+    let mut seen = vec![false; (w * h) as usize];
+    let index = |pt: &Point| (pt.y * w as i64 + pt.x) as usize;
+    let mut out = Vec::new();
+    for y in 0..h as i64 {
+        for x in 0..w as i64 {
+            let start = Point { x, y };
+            if seen[index(&start)] || !board[start.clone()] {
+                continue;
+            }
+            seen[index(&start)] = true;
+            let mut island = vec![start.clone()];
+            let mut queue = vec![start];
+            while let Some(pt) = queue.pop() {
+                for (dx, dy) in (-2..=2).flat_map(|dy| (-2..=2).map(move |dx| (dx, dy))) {
+                    let next = pt.clone() + Point { x: dx, y: dy };
+                    let Some(wrapped) = topology.wrap(&next, w, h) else {
+                        continue;
+                    };
+                    if !seen[index(&wrapped)] && board[wrapped.clone()] {
+                        seen[index(&wrapped)] = true;
+                        island.push(next.clone());
+                        queue.push(next);
+                    }
+                }
+            }
+            out.push(island);
+        }
+    }
+    out
+}
+
+/// Cells moved so the bounding box starts at the origin, in sorted order
+fn normalized(cells: &[Point]) -> Vec<Point> {
+    let min_x = cells.iter().map(|p| p.x).min().unwrap_or(0);
+    let min_y = cells.iter().map(|p| p.y).min().unwrap_or(0);
+    let mut out: Vec<Point> = cells
+        .iter()
+        .map(|p| Point {
+            x: p.x - min_x,
+            y: p.y - min_y,
+        })
+        .collect();
+    out.sort();
+    out
+}
+
+/// Extended Wechsler format of normalized cells: strips of five rows, each
+/// column a base 32 digit, with runs of empty columns shortened
+fn wechsler(cells: &[Point]) -> String {
+    let width = cells.iter().map(|p| p.x + 1).max().unwrap_or(0);
+    let height = cells.iter().map(|p| p.y + 1).max().unwrap_or(0);
+    let mut strips = Vec::new();
+    for strip in 0..(height + 4) / 5 {
+        let mut cols = vec![0u8; width as usize];
+        for p in cells.iter().filter(|p| p.y / 5 == strip) {
+            cols[p.x as usize] |= 1 << (p.y % 5);
+        }
+        while cols.last() == Some(&0) {
+            cols.pop();
+        }
+        let mut out = String::new();
+        let mut zeros = 0;
+        for c in cols.into_iter().chain(std::iter::once(1)) {
+            if c == 0 {
+                zeros += 1;
+                continue;
+            }
+            while zeros > 0 {
+                let run = zeros.min(39);
+                match run {
+                    1 => out.push('0'),
+                    2 => out.push('w'),
+                    3 => out.push('x'),
+                    n => {
+                        out.push('y');
+                        out.push(DIGITS[n - 4] as char);
+                    }
+                }
+                zeros -= run;
+            }
+            out.push(DIGITS[c as usize] as char);
+        }
+        // drop the sentinel column
+        out.pop();
+        strips.push(out);
+    }
+    strips.join("z")
+}
+
+/// Shortest, then lowest, Wechsler code over every rotation and reflection
+fn canonical(cells: &[Point]) -> String {
+    (0..8)
+        .map(|t| {
+            let turned: Vec<Point> = cells
+                .iter()
+                .map(|p| {
+                    let (x, y) = if t & 4 != 0 { (p.y, p.x) } else { (p.x, p.y) };
+                    Point {
+                        x: if t & 1 != 0 { -x } else { x },
+                        y: if t & 2 != 0 { -y } else { y },
+                    }
+                })
+                .collect();
+            wechsler(&normalized(&turned))
+        })
+        .min_by(|a, b| a.len().cmp(&b.len()).then_with(|| a.cmp(b)))
+        .unwrap_or_default()
+}
+
+/// apgcode of an island run on its own: `xs` still lifes with their
+/// population, `xp` oscillators and `xq` spaceships with their period, run
+/// on a copy of the empty `plane`
+fn classify(island: &[Point], plane: &Plane) -> String {
+    let mut plane = plane.clone();
+    for pt in island {
+        plane.set(pt.clone(), true);
+    }
+    let first = normalized(island);
+    let mut phases = vec![first.clone()];
+    let corner = |cells: &[Point]| {
+        (
+            cells.iter().map(|p| p.x).min().unwrap_or(0),
+            cells.iter().map(|p| p.y).min().unwrap_or(0),
+        )
+    };
+    let start = corner(island);
+    for period in 1..=MAX_PERIOD {
+        plane.step();
+        let cells = plane.cells();
+        if cells.is_empty() {
+            break;
+        }
+        let phase = normalized(&cells);
+        if phase != first {
+            phases.push(phase);
+            continue;
+        }
+        let code = phases
+            .iter()
+            .map(|p| canonical(p))
+            .min_by(|a, b| a.len().cmp(&b.len()).then_with(|| a.cmp(b)))
+            .unwrap_or_default();
+        return match (period, corner(&cells) == start) {
+            (1, true) => format!("xs{}_{}", first.len(), code),
+            (_, true) => format!("xp{}_{}", period, code),
+            (_, false) => format!("xq{}_{}", period, code),
+        };
+    }
+    UNKNOWN.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn board(rows: &[&str]) -> Board {
+        let width = rows[0].len() as u32;
+        Board::new(
+            width,
+            rows.iter()
+                .flat_map(|r| r.chars().map(|c| c == 'o'))
+                .collect(),
+        )
+    }
+    fn code(rows: &[&str]) -> String {
+        // padded so the island can't reach itself around the torus
+        let blank = ".".repeat(rows[0].len() + 4);
+        let padded: Vec<String> = [blank.clone(), blank.clone()]
+            .into_iter()
+            .chain(rows.iter().map(|r| format!("..{}..", r)))
+            .chain([blank.clone(), blank])
+            .collect();
+        let b = board(&padded.iter().map(String::as_str).collect::<Vec<_>>());
+        let islands = islands(&b);
+        assert_eq!(islands.len(), 1);
+        classify(&islands[0], &Plane::new(&LifeRule::life()).unwrap())
+    }
+
+    #[test]
+    fn test_known_codes() {
+        assert_eq!(code(&[".....", ".oo..", ".oo..", "....."]), "xs4_33");
+        assert_eq!(code(&[".....", ".ooo.", "....."]), "xp2_7");
+        assert_eq!(
+            code(&["......", "..oo..", ".o..o.", "..oo..", "......"]),
+            "xs6_696"
+        );
+        assert_eq!(code(&["..o...", "...o..", ".ooo..", "......"]), "xq4_153");
+        assert_eq!(
+            code(&["......", "..oo..", ".o..o.", "..o.o.", "...o..", "......"]),
+            "xs7_2596"
+        );
+        assert_eq!(code(&["......", ".ooo..", "..ooo.", "......"]), "xp2_7e");
+        assert_eq!(
+            code(&["......", ".oo...", ".oo...", "...oo.", "...oo.", "......"]),
+            "xp2_318c"
+        );
+        assert_eq!(
+            code(&["........", "..o..o..", ".o......", ".o...o..", ".oooo...", "........"]),
+            "xq4_6frc"
+        );
+    }
+    #[test]
+    fn test_wechsler_runs() {
+        let cells = [
+            Point { x: 0, y: 0 },
+            Point { x: 9, y: 0 },
+            Point { x: 0, y: 6 },
+        ];
+        assert_eq!(wechsler(&cells), "1y41z2");
+    }
+    #[test]
+    fn test_tally() -> Result<()> {
+        // two blocks, one of them across the edge of the torus, and a blinker
+        let b = board(&[
+            "o.........o",
+            "o.........o",
+            "...........",
+            "...oo......",
+            "...oo..ooo.",
+            "...........",
+            "...........",
+            "...........",
+        ]);
+        let census = Census::take(&b, &LifeRule::life())?;
+        assert_eq!(census.tally(), vec![("xs4_33", 2), ("xp2_7", 1)]);
+        assert_eq!(census.name("xs4_33"), Some("block"));
+        assert_eq!(census.lines()[1], "     1 xp2_7 (blinker)");
+        Ok(())
+    }
+    #[test]
+    fn test_check() -> Result<()> {
+        assert!(Census::check(&LifeRule::life(), Topology::Plane).is_ok());
+        for rule in ["B03/S23", "B2/S/C3", "B2-a/S12"] {
+            assert!(Census::check(&rule.parse()?, Topology::Torus).is_err());
+        }
+        let klein = Topology::Klein { horizontal: true };
+        assert!(Census::check(&LifeRule::life(), klein).is_err());
+        let b = board(&["o.", ".."]).with_topology(Topology::Sphere);
+        assert!(Census::take(&b, &LifeRule::life()).is_err());
+        Ok(())
+    }
+}
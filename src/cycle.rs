@@ -0,0 +1,124 @@
+use clap::{builder::PossibleValue, ValueEnum};
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap, VecDeque},
+    fmt::Display,
+    hash::{Hash, Hasher},
+};
+
+/// Periodic behaviour found by a [`Detector`], a period of 1 is a board that
+/// has stopped changing
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Cycle {
+    /// First generation of the cycle
+    pub start: u64,
+    pub period: u64,
+}
+
+impl Display for Cycle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "period {} from generation {}", self.period, self.start)
+    }
+}
+
+/// What the simulation does once the board has become periodic
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum OnCycle {
+    #[default]
+    Continue,
+    Pause,
+    Exit,
+}
+
+/// Spots a repeated board by remembering the hashes of the last few
+/// generations, cycles longer than the history go unnoticed. Boards aren't
+/// kept to be compared, so detection is probabilistic: two different boards
+/// with the same 64 bit hash would be taken for a cycle
+pub struct Detector {
+    seen: HashMap<u64, u64>,
+    order: VecDeque<u64>,
+    capacity: usize,
+}
+
+impl Detector {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            seen: HashMap::with_capacity(capacity),
+            order: VecDeque::with_capacity(capacity),
+            capacity: capacity.max(1),
+        }
+    }
+    /// Record `state` as the board at `generation`, returns the cycle it
+    /// closes if it was seen before
+    pub fn observe(&mut self, state: &impl Hash, generation: u64) -> Option<Cycle> {
+        let mut hasher = DefaultHasher::new();
+        state.hash(&mut hasher);
+        let hash = hasher.finish();
+        if let Some(start) = self.seen.get(&hash) {
+            return Some(Cycle {
+                start: *start,
+                period: generation - start,
+            });
+        }
+        if self.order.len() == self.capacity {
+            let old = self.order.pop_front().unwrap();
+            self.seen.remove(&old);
+        }
+        self.order.push_back(hash);
+        self.seen.insert(hash, generation);
+        None
+    }
+}
+
+impl ValueEnum for OnCycle {
+    fn value_variants<'a>() -> &'a [Self] {
+        &[OnCycle::Continue, OnCycle::Pause, OnCycle::Exit]
+    }
+
+    fn to_possible_value(&self) -> Option<clap::builder::PossibleValue> {
+        Some(match self {
+            OnCycle::Continue => PossibleValue::new("continue"),
+            OnCycle::Pause => PossibleValue::new("pause"),
+            OnCycle::Exit => PossibleValue::new("exit"),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_finds_cycle() {
+        let mut d = Detector::new(8);
+        let states = [5, 6, 1, 2, 3, 1];
+        let found: Vec<_> = states
+            .iter()
+            .enumerate()
+            .map(|(g, s)| d.observe(s, g as u64))
+            .collect();
+        assert!(found[..5].iter().all(Option::is_none));
+        assert_eq!(
+            found[5],
+            Some(Cycle {
+                start: 2,
+                period: 3
+            })
+        );
+    }
+    #[test]
+    fn test_history_is_bounded() {
+        let mut d = Detector::new(2);
+        for (g, s) in [1, 2, 3].iter().enumerate() {
+            assert_eq!(d.observe(s, g as u64), None);
+        }
+        // 1 fell out of the history
+        assert_eq!(d.observe(&1, 3), None);
+        assert_eq!(
+            d.observe(&3, 4),
+            Some(Cycle {
+                start: 2,
+                period: 2
+            })
+        );
+    }
+}
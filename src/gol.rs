@@ -1,16 +1,57 @@
+use crate::{neighborhood::Neighborhood, topology::Topology};
 use anyhow::Result;
 use std::{
+    fmt::Debug,
     fmt::Display,
-    ops::{Add, Index, IndexMut},
+    hash::{Hash, Hasher},
+    marker::PhantomData,
+    ops::{Add, Index},
 };
 
+/// State a cell can be in. States are stored as small codes: 0 is the
+/// empty state, 1 the live one that fast paths and population counts look
+/// at, and the rest are rule specific
+pub trait CellState: Copy + Default + Eq + Hash + Debug + Send + Sync {
+    fn code(self) -> u8;
+    /// State stored as `code`, codes the state doesn't use read as empty
+    fn from_code(code: u8) -> Self;
+}
+
+impl CellState for u8 {
+    fn code(self) -> u8 {
+        self
+    }
+    fn from_code(code: u8) -> Self {
+        code
+    }
+}
+
+impl CellState for bool {
+    fn code(self) -> u8 {
+        self as u8
+    }
+    fn from_code(code: u8) -> Self {
+        code == 1
+    }
+}
+
+/// Grid of cells in some [`CellState`], raw state codes by default
 #[derive(Clone, Debug)]
-pub struct Board {
-    buf: Vec<bool>,
+pub struct Board<S: CellState = u8> {
+    /// Live cells packed 64 to a word, every row starts on a new word
+    buf: Vec<u64>,
     width: u32,
+    height: u32,
+    /// Per cell code of states past 1 for multi-state rules (the dying
+    /// states of Generations), 0 for cells which are alive or dead. Empty
+    /// until such a state is first set
+    dying: Vec<u8>,
+    /// How cells past the edges map back onto the board
+    topology: Topology,
+    cell: PhantomData<S>,
 }
 
-#[derive(Clone, Debug, PartialEq, PartialOrd, Eq, Ord)]
+#[derive(Clone, Debug, PartialEq, PartialOrd, Eq, Ord, Hash)]
 pub struct Point {
     pub x: i64,
     pub y: i64,
@@ -77,134 +118,393 @@ impl Display for Mask {
     }
 }
 
+const WORD: u32 = u64::BITS;
+
+/// Offsets of the eight cells around a cell in reading order
+const NEIGHBORS: [(i64, i64); 8] = [
+    (-1, -1),
+    (0, -1),
+    (1, -1),
+    (-1, 0),
+    (1, 0),
+    (-1, 1),
+    (0, 1),
+    (1, 1),
+];
+
+/// Changes which turn one board into another of the same size, only the
+/// packed words and dying states that differ are kept
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Diff {
+    /// Word index and the bits which flipped in it
+    words: Vec<(u32, u64)>,
+    /// Cell index and its new dying state
+    dying: Vec<(u32, u8)>,
+}
+
+impl Diff {
+    /// Approximate memory used by the diff
+    pub fn bytes(&self) -> usize {
+        self.words.len() * std::mem::size_of::<(u32, u64)>()
+            + self.dying.len() * std::mem::size_of::<(u32, u8)>()
+    }
+}
+
 impl Board {
-    pub fn new(width: u32, buf: Vec<bool>) -> Self {
-        Board { width, buf }
-    }
-    fn pt_to_index(&self, mut pt: Point) -> usize {
-        let orig = pt.clone();
-        pt.remap(self.width(), self.height());
-        let index = ((pt.y * self.width as i64) + pt.x) as usize;
-        assert!(
-            index < (self.width() * self.height()) as usize,
-            "pt {} inside bounds (adj: {})",
-            pt,
-            orig
-        );
-        index
+    pub fn new(width: u32, cells: Vec<bool>) -> Self {
+        let height = cells.len() as u32 / width;
+        let mut board = Self::empty(width, height);
+        for (i, c) in cells.into_iter().enumerate() {
+            if c {
+                let (word, bit) = board.word_of(i);
+                board.buf[word] |= 1 << bit;
+            }
+        }
+        board
+    }
+    pub fn empty(width: u32, height: u32) -> Self {
+        Board {
+            buf: vec![0; (width.div_ceil(WORD) * height) as usize],
+            width,
+            height,
+            dying: Vec::new(),
+            topology: Topology::default(),
+            cell: PhantomData,
+        }
+    }
+    /// Build a board from cell states, 0 is dead, 1 alive and anything
+    /// higher is dying
+    pub fn from_states(width: u32, states: Vec<u8>) -> Self {
+        let mut board = Self::new(width, states.iter().map(|s| *s == 1).collect());
+        if states.iter().any(|s| *s > 1) {
+            board.dying = states
+                .into_iter()
+                .map(|s| if s > 1 { s } else { 0 })
+                .collect();
+        }
+        board
+    }
+    /// Build a board from packed rows, see [`Board::row`]
+    pub fn from_rows(width: u32, mut buf: Vec<u64>) -> Self {
+        let stride = width.div_ceil(WORD) as usize;
+        let height = (buf.len() / stride) as u32;
+        let tail = Self::tail_mask(width);
+        for row in buf.chunks_mut(stride) {
+            row[stride - 1] &= tail;
+        }
+        Board {
+            buf,
+            width,
+            height,
+            dying: Vec::new(),
+            topology: Topology::default(),
+            cell: PhantomData,
+        }
+    }
+    /// Same cells centred on a `width` by `height` board, cropped where it
+    /// is smaller
+    pub fn resized(&self, width: u32, height: u32) -> Self {
+        let mut sized = Board::empty(width, height);
+        let dx = (width as i64 - self.width as i64) / 2;
+        let dy = (height as i64 - self.height as i64) / 2;
+        for y in 0..height as i64 {
+            for x in 0..width as i64 {
+                let src = Point {
+                    x: x - dx,
+                    y: y - dy,
+                };
+                if (0..self.width as i64).contains(&src.x)
+                    && (0..self.height as i64).contains(&src.y)
+                {
+                    sized.set_state(Point { x, y }, self.state(src));
+                }
+            }
+        }
+        sized
+    }
+}
+
+impl<S: CellState> Board<S> {
+    /// Build a board from cells in row-major order
+    pub fn from_cells(width: u32, cells: Vec<S>) -> Self {
+        Board::from_states(width, cells.into_iter().map(S::code).collect()).cast()
+    }
+    /// Same cells read as another kind of state
+    pub fn cast<T: CellState>(self) -> Board<T> {
+        Board {
+            buf: self.buf,
+            width: self.width,
+            height: self.height,
+            dying: self.dying,
+            topology: self.topology,
+            cell: PhantomData,
+        }
+    }
+    /// Same board with its edges joined according to `topology`
+    pub fn with_topology(mut self, topology: Topology) -> Self {
+        self.topology = topology;
+        self
+    }
+    pub fn topology(&self) -> Topology {
+        self.topology
+    }
+    /// Live cells of the last word of each row, the bits past the width are
+    /// always kept clear
+    fn tail_mask(width: u32) -> u64 {
+        match width % WORD {
+            0 => !0,
+            n => (1 << n) - 1,
+        }
+    }
+    /// Words per row
+    fn stride(&self) -> usize {
+        self.width.div_ceil(WORD) as usize
+    }
+    /// Live cells of row `y` packed 64 per word, bit `x % 64` of word
+    /// `x / 64` being the cell at `x`
+    pub fn row(&self, y: u32) -> &[u64] {
+        let stride = self.stride();
+        &self.buf[y as usize * stride..(y as usize + 1) * stride]
+    }
+    fn word_of(&self, index: usize) -> (usize, u32) {
+        let (x, y) = (index as u32 % self.width, index as u32 / self.width);
+        (y as usize * self.stride() + (x / WORD) as usize, x % WORD)
+    }
+    /// Index of the cell `pt` refers to under the board's topology, `None`
+    /// if it is past an edge which is not joined to another
+    fn pt_to_index(&self, pt: Point) -> Option<usize> {
+        let pt = self.topology.wrap(&pt, self.width, self.height)?;
+        Some(((pt.y * self.width as i64) + pt.x) as usize)
+    }
+    fn get(&self, index: usize) -> bool {
+        let (word, bit) = self.word_of(index);
+        self.buf[word] & (1 << bit) != 0
+    }
+    pub fn set(&mut self, pt: Point, alive: bool) {
+        self.set_state(pt, alive as u8)
     }
-    #[allow(dead_code)]
     pub fn slice(&self, sect: &Mask) -> Result<Self> {
-        let mut out = Vec::new();
-        out.reserve((sect.w * sect.h) as usize);
+        let mut out = Vec::with_capacity((sect.w * sect.h) as usize);
         for y in sect.y..sect.bottom() {
             for x in sect.x..sect.right() {
-                out.push(
-                    self[Point {
-                        x: x as i64,
-                        y: y as i64,
-                    }],
-                );
+                out.push(self.state(Point {
+                    x: x as i64,
+                    y: y as i64,
+                }));
             }
         }
-        Ok(Self {
-            buf: out,
-            width: sect.w,
-        })
+        Ok(Board::from_states(sect.w, out)
+            .cast()
+            .with_topology(self.topology))
+    }
+
+    /// State of every cell in row-major order, see [`Board::from_states`]
+    pub fn states(&self) -> Vec<u8> {
+        (0..(self.width * self.height) as usize)
+            .map(|i| self.state_at(i))
+            .collect()
+    }
+    fn state_at(&self, index: usize) -> u8 {
+        if self.get(index) {
+            1
+        } else {
+            self.dying.get(index).copied().unwrap_or(0)
+        }
+    }
+    pub fn state(&self, pt: Point) -> u8 {
+        self.pt_to_index(pt).map_or(0, |i| self.state_at(i))
+    }
+    /// Cell at `pt`, empty outside the board
+    pub fn cell(&self, pt: Point) -> S {
+        S::from_code(self.state(pt))
+    }
+    pub fn set_cell(&mut self, pt: Point, cell: S) {
+        self.set_state(pt, cell.code())
+    }
+    pub fn set_state(&mut self, pt: Point, state: u8) {
+        let Some(idx) = self.pt_to_index(pt) else {
+            return;
+        };
+        let (word, bit) = self.word_of(idx);
+        if state == 1 {
+            self.buf[word] |= 1 << bit;
+        } else {
+            self.buf[word] &= !(1 << bit);
+        }
+        if state > 1 && self.dying.is_empty() {
+            self.dying = vec![0; (self.width * self.height) as usize];
+        }
+        if let Some(d) = self.dying.get_mut(idx) {
+            *d = if state > 1 { state } else { 0 };
+        }
     }
 
     pub fn width(&self) -> u32 {
-        return self.width;
+        self.width
     }
     pub fn height(&self) -> u32 {
-        return (self.buf.len() / (self.width() as usize)) as u32;
+        self.height
     }
-    pub fn neighbors(&self, pt: &Point) -> [bool; 8] {
-        let pts = [
-            Point {
-                x: pt.x + 1,
-                y: pt.y,
-            },
-            Point {
-                x: pt.x - 1,
-                y: pt.y,
-            },
-            Point {
-                x: pt.x,
-                y: pt.y + 1,
-            },
-            Point {
-                x: pt.x,
-                y: pt.y - 1,
-            },
-            Point {
-                x: pt.x + 1,
-                y: pt.y - 1,
-            },
-            Point {
-                x: pt.x + 1,
-                y: pt.y + 1,
-            },
-            Point {
-                x: pt.x - 1,
-                y: pt.y + 1,
-            },
-            Point {
-                x: pt.x - 1,
-                y: pt.y - 1,
-            },
-        ];
-        pts.map(|p| self[p])
+    /// Live state of the eight cells around `pt` in reading order (north
+    /// west to south east), cells outside of `hood` always read as dead
+    pub fn neighbors(&self, pt: &Point, hood: Neighborhood) -> [bool; 8] {
+        NEIGHBORS.map(|(x, y)| {
+            let d = Point { x, y };
+            hood.contains(1, &d) && self[pt.clone() + d]
+        })
+    }
+
+    /// The eight cells around `pt` in the same order as
+    /// [`Board::neighbors`], cells outside of `hood` read as empty
+    pub fn neighbor_cells(&self, pt: &Point, hood: Neighborhood) -> [S; 8] {
+        NEIGHBORS.map(|(x, y)| {
+            let d = Point { x, y };
+            if hood.contains(1, &d) {
+                self.cell(pt.clone() + d)
+            } else {
+                S::default()
+            }
+        })
     }
 
-    #[allow(dead_code)]
     pub fn pixels(&self) -> Vec<(Point, bool)> {
-        self.buf
-            .iter()
-            .enumerate()
-            .map(|(i, b)| {
+        (0..(self.width * self.height) as usize)
+            .map(|i| {
                 (
                     Point {
-                        x: (i as u32 % self.height()) as i64,
+                        x: (i as u32 % self.width()) as i64,
                         y: (i as u32 / self.width()) as i64,
                     },
-                    *b,
+                    self.get(i),
                 )
             })
             .collect()
     }
-    pub fn alive(&self) -> usize {
-        self.buf.iter().filter(|v| **v).count()
+    /// Approximate memory used by the board's cells
+    pub fn bytes(&self) -> usize {
+        self.buf.len() * std::mem::size_of::<u64>() + self.dying.len()
     }
-    pub fn pixels_mut(&mut self) -> Vec<(Point, &mut bool)> {
-        let h = self.height();
-        let w = self.width();
-        self.buf
-            .iter_mut()
+    /// Changes from `self` to `next`, `None` if they differ in size
+    pub fn diff(&self, next: &Self) -> Option<Diff> {
+        if (self.width, self.height) != (next.width, next.height) {
+            return None;
+        }
+        let words = self
+            .buf
+            .iter()
+            .zip(&next.buf)
             .enumerate()
-            .map(|(i, b)| {
-                (
-                    Point {
-                        x: (i as u32 % h) as i64,
-                        y: (i as u32 / w) as i64,
-                    },
-                    b,
-                )
-            })
-            .collect()
+            .filter(|(_, (a, b))| a != b)
+            .map(|(i, (a, b))| (i as u32, a ^ b))
+            .collect();
+        let dying = if self.dying.is_empty() && next.dying.is_empty() {
+            Vec::new()
+        } else {
+            (0..(self.width * self.height) as usize)
+                .filter_map(|i| {
+                    let (a, b) = (self.dying.get(i), next.dying.get(i));
+                    let b = b.copied().unwrap_or(0);
+                    (a.copied().unwrap_or(0) != b).then_some((i as u32, b))
+                })
+                .collect()
+        };
+        Some(Diff { words, dying })
+    }
+    /// Apply changes from [`Board::diff`]
+    pub fn patch(&mut self, diff: &Diff) {
+        for (i, flipped) in &diff.words {
+            self.buf[*i as usize] ^= flipped;
+        }
+        if !diff.dying.is_empty() && self.dying.is_empty() {
+            self.dying = vec![0; (self.width * self.height) as usize];
+        }
+        for (i, state) in &diff.dying {
+            self.dying[*i as usize] = *state;
+        }
+    }
+    /// Cells which came alive and cells which stopped being alive going
+    /// from `self` to `next`, `None` if they differ in size
+    pub fn changes(&self, next: &Self) -> Option<(u64, u64)> {
+        if (self.width, self.height) != (next.width, next.height) {
+            return None;
+        }
+        Some(
+            self.buf
+                .iter()
+                .zip(&next.buf)
+                .fold((0, 0), |(born, died), (a, b)| {
+                    (
+                        born + (!a & b).count_ones() as u64,
+                        died + (a & !b).count_ones() as u64,
+                    )
+                }),
+        )
+    }
+    /// Top left and bottom right live cells of the smallest rectangle
+    /// holding them all, `None` if there are none
+    pub fn bounds(&self) -> Option<(Point, Point)> {
+        let stride = self.stride();
+        let rows: Vec<u32> = (0..self.height)
+            .filter(|y| self.row(*y).iter().any(|w| *w != 0))
+            .collect();
+        let (top, bottom) = (*rows.first()?, *rows.last()?);
+        let column = |y: u32, first: bool| {
+            let row = self.row(y);
+            let word = if first {
+                row.iter().position(|w| *w != 0)?
+            } else {
+                stride - 1 - row.iter().rev().position(|w| *w != 0)?
+            };
+            let bit = if first {
+                row[word].trailing_zeros()
+            } else {
+                WORD - 1 - row[word].leading_zeros()
+            };
+            Some(word as i64 * WORD as i64 + bit as i64)
+        };
+        let left = rows.iter().filter_map(|y| column(*y, true)).min()?;
+        let right = rows.iter().filter_map(|y| column(*y, false)).max()?;
+        Some((
+            Point {
+                x: left,
+                y: top as i64,
+            },
+            Point {
+                x: right,
+                y: bottom as i64,
+            },
+        ))
+    }
+    pub fn alive(&self) -> usize {
+        self.buf.iter().map(|w| w.count_ones() as usize).sum()
+    }
+    pub fn dying(&self) -> usize {
+        self.dying.iter().filter(|v| **v != 0).count()
     }
 }
 
-impl Index<Point> for Board {
-    type Output = bool;
-    fn index(&self, index: Point) -> &Self::Output {
-        return &self.buf[self.pt_to_index(index)];
+/// Cells only, two boards with the same cells hash alike whatever their
+/// topology
+impl<S: CellState> Hash for Board<S> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.width.hash(state);
+        self.buf.hash(state);
+        // the dying states are only kept once a board has had some, so an
+        // all dead vector counts the same as none
+        if self.dying.iter().any(|d| *d != 0) {
+            self.dying.hash(state);
+        }
     }
 }
-impl IndexMut<Point> for Board {
-    fn index_mut(&mut self, index: Point) -> &mut Self::Output {
-        let idx = self.pt_to_index(index);
-        return &mut self.buf[idx];
+
+impl<S: CellState> Index<Point> for Board<S> {
+    type Output = bool;
+    fn index(&self, index: Point) -> &Self::Output {
+        if self.pt_to_index(index).is_some_and(|i| self.get(i)) {
+            &true
+        } else {
+            &false
+        }
     }
 }
 
@@ -231,10 +531,11 @@ mod tests {
     use super::*;
 
     #[test]
+    #[allow(clippy::bool_assert_comparison)]
     fn test_slice_board() -> Result<()> {
         let init = Board::new(4, (0..16).map(|_| false).collect());
         let mut whole = init.clone();
-        whole[Point { x: 1, y: 1 }] = true;
+        whole.set(Point { x: 1, y: 1 }, true);
         let sliced = whole.slice(&Mask {
             x: 1,
             y: 1,
@@ -247,6 +548,116 @@ mod tests {
         Ok(())
     }
     #[test]
+    fn test_dying_states() {
+        let mut b = Board::new(3, vec![false; 9]);
+        assert_eq!(b.state(Point { x: 1, y: 1 }), 0);
+        b.set_state(Point { x: 1, y: 1 }, 3);
+        b.set_state(Point { x: 2, y: 1 }, 1);
+        assert_eq!(b.state(Point { x: 1, y: 1 }), 3);
+        assert!(!b[Point { x: 1, y: 1 }]);
+        assert!(b[Point { x: 2, y: 1 }]);
+        assert_eq!((b.alive(), b.dying()), (1, 1));
+        b.set_state(Point { x: 1, y: 1 }, 0);
+        assert_eq!(b.state(Point { x: 1, y: 1 }), 0);
+        assert_eq!(b.dying(), 0);
+
+        // a board whose dying cells are all gone hashes like one which
+        // never had any
+        let mut fresh = Board::new(3, vec![false; 9]);
+        fresh.set_state(Point { x: 2, y: 1 }, 1);
+        let hash = |b: &Board| {
+            let mut hasher = std::collections::hash_map::DefaultHasher::new();
+            b.hash(&mut hasher);
+            hasher.finish()
+        };
+        assert_eq!(hash(&b), hash(&fresh));
+    }
+    #[test]
+    fn test_resized() {
+        let b = Board::from_states(2, vec![1, 0, 2, 1]);
+        let padded = b.resized(4, 5);
+        assert_eq!(padded.states()[5..11], [1, 0, 0, 0, 2, 1]);
+        assert_eq!((padded.alive(), padded.dying()), (2, 1));
+        assert_eq!(padded.resized(2, 2).states(), b.states());
+    }
+    #[test]
+    fn test_neighbors_masked_by_hood() {
+        let board = Board::new(3, vec![true; 9]);
+        let center = Point { x: 1, y: 1 };
+        let count = |hood| {
+            board
+                .neighbors(&center, hood)
+                .iter()
+                .filter(|b| **b)
+                .count()
+        };
+        assert_eq!(count(Neighborhood::Moore), 8);
+        assert_eq!(count(Neighborhood::VonNeumann), 4);
+        assert_eq!(count(Neighborhood::Hexagonal), 6);
+    }
+    #[test]
+    fn test_packed_rows() {
+        let mut b = Board::new(70, vec![false; 140]);
+        b.set(Point { x: 69, y: 0 }, true);
+        b.set(Point { x: 64, y: 1 }, true);
+        b.set(Point { x: 0, y: 1 }, true);
+        assert_eq!(b.row(0), &[0, 1 << 5]);
+        assert_eq!(b.row(1), &[1, 1]);
+        assert!(b[Point { x: -1, y: 0 }]);
+        assert_eq!(b.alive(), 3);
+        b.set(Point { x: 69, y: 0 }, false);
+        assert_eq!(b.alive(), 2);
+        // bits past the width are dropped
+        let b = Board::from_rows(3, vec![!0, 0]);
+        assert_eq!(b.row(0), &[0b111]);
+        assert_eq!(b.alive(), 3);
+    }
+    #[test]
+    fn test_topology_neighbors() {
+        let mut b = Board::new(4, vec![false; 16]);
+        b.set(Point { x: 3, y: 3 }, true);
+        let corner = |b: &Board| b.neighbors(&Point { x: 0, y: 0 }, Neighborhood::Moore)[0];
+        assert!(corner(&b));
+        let b = b.with_topology(Topology::Plane);
+        assert!(!corner(&b));
+        assert!(!b[Point { x: -1, y: -1 }]);
+        let mut b = b.with_topology(Topology::Klein { horizontal: true });
+        // crossing the top edge mirrors the column
+        b.set(Point { x: 1, y: -1 }, true);
+        assert!(b[Point { x: 2, y: 3 }]);
+    }
+    #[test]
+    fn test_diff_patch() {
+        let mut a = Board::new(70, vec![false; 140]);
+        a.set(Point { x: 3, y: 0 }, true);
+        let mut b = a.clone();
+        b.set(Point { x: 3, y: 0 }, false);
+        b.set(Point { x: 66, y: 1 }, true);
+        b.set_state(Point { x: 5, y: 1 }, 2);
+        let diff = a.diff(&b).unwrap();
+        assert_eq!(diff.words.len(), 2);
+        a.patch(&diff);
+        assert_eq!(a.states(), b.states());
+        assert!(a.diff(&Board::empty(3, 3)).is_none());
+    }
+    #[test]
+    fn test_changes_and_bounds() {
+        let mut a = Board::new(70, vec![false; 210]);
+        assert_eq!(a.bounds(), None);
+        a.set(Point { x: 66, y: 0 }, true);
+        a.set(Point { x: 3, y: 2 }, true);
+        assert_eq!(
+            a.bounds(),
+            Some((Point { x: 3, y: 0 }, Point { x: 66, y: 2 }))
+        );
+        let mut b = a.clone();
+        b.set(Point { x: 66, y: 0 }, false);
+        b.set(Point { x: 1, y: 1 }, true);
+        b.set(Point { x: 2, y: 1 }, true);
+        assert_eq!(a.changes(&b), Some((2, 1)));
+    }
+    #[test]
+    #[allow(clippy::unnecessary_cast)]
     fn test_remap() {
         let w = 10 as i64;
         let h = 10 as i64;
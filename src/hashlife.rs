@@ -0,0 +1,497 @@
+use anyhow::{ensure, Result};
+use std::collections::HashMap;
+
+use crate::{
+    gol::{Board, Point},
+    macrocell::{self, Macrocell, Node as McNode},
+    rule::LifeRule,
+};
+
+type Id = u32;
+
+const DEAD: Id = 0;
+const ALIVE: Id = 1;
+
+/// Nodes kept before unreachable ones are dropped along with the result cache
+const MAX_NODES: usize = 1 << 22;
+
+/// Largest step, as a power of two, keeping coordinates within an `i64`
+pub const MAX_STEP: u8 = 48;
+
+#[derive(Clone, Copy, Debug)]
+struct Node {
+    level: u8,
+    /// North west, north east, south west and south east quadrants
+    quads: [Id; 4],
+    population: u64,
+}
+
+/// HashLife universe: an unbounded plane stored as a quadtree where equal
+/// subtrees are shared and the result of advancing each node is memoized,
+/// so repetitive patterns can be run for very large numbers of generations.
+///
+/// Unlike [`Board`] the plane does not wrap, cells which would go off one
+/// edge of the board's torus keep going instead
+pub struct Universe {
+    rule: LifeRule,
+    nodes: Vec<Node>,
+    index: HashMap<[Id; 4], Id>,
+    /// Centered result of advancing a node by `2^k` generations, keyed on
+    /// the node and `k`
+    results: HashMap<(Id, u8), Id>,
+    empties: Vec<Id>,
+    /// Root of the tree, centered on the origin
+    root: Id,
+    generation: u64,
+}
+
+impl Universe {
+    pub fn new(rule: &LifeRule) -> Result<Self> {
+        ensure!(
+            rule.states() == 2,
+            "hashlife needs a two-state rule, {} has {} states",
+            rule,
+            rule.states()
+        );
+        ensure!(
+            !rule.get(0),
+            "{} fills every empty cell of the plane, hashlife can't run B0 rules",
+            rule
+        );
+        let leaf = |population| Node {
+            level: 0,
+            quads: [DEAD; 4],
+            population,
+        };
+        let mut universe = Self {
+            rule: rule.clone(),
+            nodes: vec![leaf(0), leaf(1)],
+            index: HashMap::new(),
+            results: HashMap::new(),
+            empties: vec![DEAD],
+            root: DEAD,
+            generation: 0,
+        };
+        universe.root = universe.empty(3);
+        Ok(universe)
+    }
+    /// Copy of `board` with its top left corner at the origin
+    pub fn from_board(board: &Board, rule: &LifeRule) -> Result<Self> {
+        let mut universe = Self::new(rule)?;
+        let size = board.width().max(board.height()) as u64;
+        let mut level = 3;
+        while 1 << (level - 1) < size {
+            level += 1;
+        }
+        let half = 1i64 << (level - 1);
+        universe.root = universe.build(board, level, Point { x: -half, y: -half });
+        Ok(universe)
+    }
+    fn build(&mut self, board: &Board, level: u8, corner: Point) -> Id {
+        let size = 1i64 << level;
+        let (w, h) = (board.width() as i64, board.height() as i64);
+        if corner.x >= w || corner.y >= h || corner.x + size <= 0 || corner.y + size <= 0 {
+            return self.empty(level);
+        }
+        if level == 0 {
+            return if board[corner] { ALIVE } else { DEAD };
+        }
+        let half = size / 2;
+        let quads = [(0, 0), (half, 0), (0, half), (half, half)].map(|(x, y)| {
+            self.build(
+                board,
+                level - 1,
+                Point {
+                    x: corner.x + x,
+                    y: corner.y + y,
+                },
+            )
+        });
+        self.join(quads)
+    }
+    /// Copy of the quadtree in `mc`, whose root is centred on the origin
+    /// just like the universe's
+    pub fn from_macrocell(mc: &Macrocell, rule: &LifeRule) -> Result<Self> {
+        let mut universe = Self::new(rule)?;
+        let mut moved = HashMap::new();
+        universe.root = universe.build_macrocell(mc, mc.root(), mc.level(), &mut moved);
+        Ok(universe)
+    }
+    fn build_macrocell(
+        &mut self,
+        mc: &Macrocell,
+        id: usize,
+        level: u8,
+        moved: &mut HashMap<usize, Id>,
+    ) -> Id {
+        if let Some(n) = moved.get(&id) {
+            return *n;
+        }
+        let n = match mc.node(id) {
+            // empty nodes of every level share id 0, so aren't memoized
+            None => return self.empty(level),
+            Some(McNode::Leaf(bits)) => self.build_leaf(bits, macrocell::LEAF, 0, 0),
+            Some(McNode::Branch { level, quads }) => {
+                let quads = quads.map(|q| self.build_macrocell(mc, q, level - 1, moved));
+                self.join(quads)
+            }
+        };
+        moved.insert(id, n);
+        n
+    }
+    /// Node for the `2^level` square of an 8x8 bitmap at `(x, y)`
+    fn build_leaf(&mut self, bits: u64, level: u8, x: u32, y: u32) -> Id {
+        if level == 0 {
+            return if bits & 1 << (y * 8 + x) != 0 {
+                ALIVE
+            } else {
+                DEAD
+            };
+        }
+        let half = 1 << (level - 1);
+        let quads = [(0, 0), (half, 0), (0, half), (half, half)]
+            .map(|(dx, dy)| self.build_leaf(bits, level - 1, x + dx, y + dy));
+        self.join(quads)
+    }
+    /// The whole universe as a macrocell quadtree, for saving states too
+    /// large for a board
+    pub fn to_macrocell(&self) -> Macrocell {
+        let mut mc = Macrocell::default();
+        let mut moved = HashMap::new();
+        let root = self.copy_macrocell(&mut mc, self.root, &mut moved);
+        mc.set_root(root, self.level());
+        mc.generation = self.generation;
+        mc
+    }
+    fn copy_macrocell(&self, mc: &mut Macrocell, id: Id, moved: &mut HashMap<Id, usize>) -> usize {
+        if let Some(n) = moved.get(&id) {
+            return *n;
+        }
+        let node = self.nodes[id as usize];
+        let n = if node.population == 0 {
+            0
+        } else if node.level == macrocell::LEAF {
+            let mut bits = 0;
+            self.leaf_bits(id, 0, 0, &mut bits);
+            mc.push(McNode::Leaf(bits))
+        } else {
+            let quads = node.quads.map(|q| self.copy_macrocell(mc, q, moved));
+            mc.push(McNode::Branch {
+                level: node.level,
+                quads,
+            })
+        };
+        moved.insert(id, n);
+        n
+    }
+    fn leaf_bits(&self, id: Id, x: u32, y: u32, bits: &mut u64) {
+        let node = self.nodes[id as usize];
+        if node.level == 0 {
+            *bits |= (id as u64 & 1) << (y * 8 + x);
+            return;
+        }
+        let half = 1 << (node.level - 1);
+        for (q, (dx, dy)) in
+            node.quads
+                .into_iter()
+                .zip([(0, 0), (half, 0), (0, half), (half, half)])
+        {
+            self.leaf_bits(q, x + dx, y + dy, bits);
+        }
+    }
+    /// Cells in the rectangle from the origin to `(width, height)`
+    pub fn to_board(&self, width: u32, height: u32) -> Board {
+        self.window(&Point { x: 0, y: 0 }, width, height)
+    }
+    /// Cells in the `width` by `height` rectangle with its top left at
+    /// `corner`
+    pub fn window(&self, corner: &Point, width: u32, height: u32) -> Board {
+        let mut board = Board::empty(width, height);
+        let half = 1i64 << (self.level() - 1);
+        let root = Point {
+            x: -half - corner.x,
+            y: -half - corner.y,
+        };
+        self.fill(&mut board, self.root, root);
+        board
+    }
+    fn fill(&self, board: &mut Board, id: Id, corner: Point) {
+        let node = self.nodes[id as usize];
+        let size = 1i64 << node.level;
+        let (w, h) = (board.width() as i64, board.height() as i64);
+        if node.population == 0
+            || corner.x >= w
+            || corner.y >= h
+            || corner.x + size <= 0
+            || corner.y + size <= 0
+        {
+            return;
+        }
+        if node.level == 0 {
+            board.set(corner, true);
+            return;
+        }
+        let half = size / 2;
+        for (q, (x, y)) in node
+            .quads
+            .into_iter()
+            .zip([(0, 0), (half, 0), (0, half), (half, half)])
+        {
+            self.fill(
+                board,
+                q,
+                Point {
+                    x: corner.x + x,
+                    y: corner.y + y,
+                },
+            );
+        }
+    }
+
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+    pub fn population(&self) -> u64 {
+        self.nodes[self.root as usize].population
+    }
+    fn level(&self) -> u8 {
+        self.nodes[self.root as usize].level
+    }
+
+    fn join(&mut self, quads: [Id; 4]) -> Id {
+        if let Some(id) = self.index.get(&quads) {
+            return *id;
+        }
+        let node = Node {
+            level: self.nodes[quads[0] as usize].level + 1,
+            quads,
+            population: quads
+                .iter()
+                .map(|q| self.nodes[*q as usize].population)
+                .fold(0, u64::saturating_add),
+        };
+        let id = self.nodes.len() as Id;
+        self.nodes.push(node);
+        self.index.insert(quads, id);
+        id
+    }
+    fn empty(&mut self, level: u8) -> Id {
+        while self.empties.len() <= level as usize {
+            let e = *self.empties.last().unwrap();
+            let next = self.join([e; 4]);
+            self.empties.push(next);
+        }
+        self.empties[level as usize]
+    }
+    fn quads(&self, id: Id) -> [Id; 4] {
+        self.nodes[id as usize].quads
+    }
+    fn population_of(&self, id: Id) -> u64 {
+        self.nodes[id as usize].population
+    }
+    /// Node one level down covering the middle of `id`
+    fn centered(&mut self, id: Id) -> Id {
+        let [nw, ne, sw, se] = self.quads(id).map(|q| self.quads(q));
+        self.join([nw[3], ne[2], sw[1], se[0]])
+    }
+    /// Node one level up with `id` in the middle of it
+    fn expand(&mut self, id: Id) -> Id {
+        let e = self.empty(self.nodes[id as usize].level - 1);
+        let [nw, ne, sw, se] = self.quads(id);
+        let quads = [[e, e, e, nw], [e, e, ne, e], [e, sw, e, e], [se, e, e, e]];
+        let quads = quads.map(|q| self.join(q));
+        self.join(quads)
+    }
+
+    /// Next generation of the middle 2x2 cells of a level 2 node
+    fn step_leaves(&mut self, id: Id) -> Id {
+        let mut cells = 0u16;
+        for (i, q) in self.quads(id).into_iter().enumerate() {
+            for (j, c) in self.quads(q).into_iter().enumerate() {
+                let (x, y) = ((i % 2) * 2 + j % 2, (i / 2) * 2 + j / 2);
+                cells |= (c as u16) << (y * 4 + x);
+            }
+        }
+        let next = [(1, 1), (2, 1), (1, 2), (2, 2)].map(|(cx, cy)| {
+            let key = (0..9).fold(0, |k, i| {
+                let (x, y) = (cx + i % 3 - 1, cy + i / 3 - 1);
+                k | ((cells >> (y * 4 + x)) & 1) << i
+            });
+            self.rule.get(key) as Id
+        });
+        self.join(next)
+    }
+    /// Middle half of `id` advanced by `2^k` generations, `k` is capped at
+    /// the most a node of its size allows (two less than its level)
+    fn successor(&mut self, id: Id, k: u8) -> Id {
+        let level = self.nodes[id as usize].level;
+        let k = k.min(level - 2);
+        if self.population_of(id) == 0 {
+            return self.empty(level - 1);
+        }
+        if let Some(r) = self.results.get(&(id, k)) {
+            return *r;
+        }
+        let result = if level == 2 {
+            self.step_leaves(id)
+        } else {
+            let [nw, ne, sw, se] = self.quads(id);
+            let [a, b, c, d] = [nw, ne, sw, se].map(|q| self.quads(q));
+            let subs = [
+                nw,
+                self.join([a[1], b[0], a[3], b[2]]),
+                ne,
+                self.join([a[2], a[3], c[0], c[1]]),
+                self.join([a[3], b[2], c[1], d[0]]),
+                self.join([b[2], b[3], d[0], d[1]]),
+                sw,
+                self.join([c[1], d[0], c[3], d[2]]),
+                se,
+            ];
+            let s = subs.map(|n| self.successor(n, k));
+            let groups = [[0, 1, 3, 4], [1, 2, 4, 5], [3, 4, 6, 7], [4, 5, 7, 8]];
+            let quads = if k < level - 2 {
+                groups.map(|g| {
+                    let [p, q, r, t] = g.map(|i| self.quads(s[i]));
+                    self.join([p[3], q[2], r[1], t[0]])
+                })
+            } else {
+                groups.map(|g| {
+                    let n = self.join(g.map(|i| s[i]));
+                    self.successor(n, k)
+                })
+            };
+            self.join(quads)
+        };
+        self.results.insert((id, k), result);
+        result
+    }
+
+    /// Advance the universe by `2^k` generations, `k` being capped at
+    /// [`MAX_STEP`]
+    pub fn step(&mut self, k: u8) {
+        let k = k.min(MAX_STEP);
+        // pad until the pattern sits inside the middle quarter of the root
+        // so nothing can run off the edge in the time being stepped
+        loop {
+            let inner = self.centered(self.root);
+            let inner = self.centered(inner);
+            if self.level() >= k + 3 && self.population_of(inner) == self.population() {
+                break;
+            }
+            self.root = self.expand(self.root);
+        }
+        let next = self.successor(self.root, k);
+        self.root = self.expand(next);
+        self.generation = self.generation.saturating_add(1 << k);
+        if self.nodes.len() > MAX_NODES {
+            self.compact();
+        }
+    }
+
+    /// Drop every node which is not reachable from the root, and with them
+    /// the result cache
+    fn compact(&mut self) {
+        let mut fresh = Self::new(&self.rule).expect("rule was already checked");
+        let mut moved = HashMap::from([(DEAD, DEAD), (ALIVE, ALIVE)]);
+        fresh.root = self.copy_into(&mut fresh, self.root, &mut moved);
+        fresh.generation = self.generation;
+        *self = fresh;
+    }
+    fn copy_into(&self, fresh: &mut Self, id: Id, moved: &mut HashMap<Id, Id>) -> Id {
+        if let Some(n) = moved.get(&id) {
+            return *n;
+        }
+        let quads = self.quads(id).map(|q| self.copy_into(fresh, q, moved));
+        let n = fresh.join(quads);
+        moved.insert(id, n);
+        n
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn glider() -> Board {
+        let mut board = Board::empty(8, 8);
+        for (x, y) in [(1, 0), (2, 1), (0, 2), (1, 2), (2, 2)] {
+            board.set(Point { x, y }, true);
+        }
+        board
+    }
+
+    #[test]
+    fn test_round_trip() -> Result<()> {
+        let board = glider();
+        let universe = Universe::from_board(&board, &LifeRule::life())?;
+        assert_eq!(universe.population(), 5);
+        assert_eq!(universe.to_board(8, 8).states(), board.states());
+        Ok(())
+    }
+    #[test]
+    fn test_macrocell_round_trip() -> Result<()> {
+        let mut universe = Universe::from_board(&glider(), &LifeRule::life())?;
+        universe.step(5);
+        let mc = universe.to_macrocell();
+        assert_eq!(mc.generation, 32);
+        let copy = Universe::from_macrocell(&mc, &LifeRule::life())?;
+        assert_eq!(copy.population(), 5);
+        // 32 generations carry the glider 8 cells down and to the right
+        let corner = Point { x: 8, y: 8 };
+        assert_eq!(copy.window(&corner, 8, 8).states(), glider().states());
+        Ok(())
+    }
+    #[test]
+    fn test_glider_moves() -> Result<()> {
+        let mut universe = Universe::from_board(&glider(), &LifeRule::life())?;
+        // a glider moves one cell diagonally every four generations
+        universe.step(2);
+        assert_eq!(universe.generation(), 4);
+        let moved = universe.to_board(8, 8);
+        for (x, y) in [(2, 1), (3, 2), (1, 3), (2, 3), (3, 3)] {
+            assert!(moved[Point { x, y }], "({}, {})", x, y);
+        }
+        assert_eq!(moved.alive(), 5);
+
+        universe.step(10);
+        assert_eq!(universe.generation(), 4 + 1024);
+        assert_eq!(universe.population(), 5);
+        Ok(())
+    }
+    #[test]
+    fn test_matches_dense_steps() -> Result<()> {
+        let rule: LifeRule = "B36/S23".parse()?;
+        let mut board = Board::empty(32, 32);
+        for i in 0..60i64 {
+            board.set(
+                Point {
+                    x: 8 + (i * 7) % 13,
+                    y: 8 + (i * 5) % 11,
+                },
+                true,
+            );
+        }
+        let mut universe = Universe::from_board(&board, &rule)?;
+        universe.step(0);
+        universe.step(1);
+        universe.step(2);
+        let word_rule = crate::packed::WordRule::from_rule(&rule).unwrap();
+        let mut dense = board;
+        for _ in 0..7 {
+            dense = crate::packed::step(&dense, &word_rule);
+        }
+        // the dense board wraps, so only compare while the pattern is away
+        // from its edges
+        assert_eq!(universe.to_board(32, 32).states(), dense.states());
+        Ok(())
+    }
+    #[test]
+    fn test_rejects_generations() {
+        assert!(Universe::new(&"B2/S/C3".parse().unwrap()).is_err());
+    }
+    #[test]
+    fn test_rejects_b0() {
+        assert!(Universe::new(&"B03/S23".parse().unwrap()).is_err());
+    }
+}
@@ -0,0 +1,74 @@
+use anyhow::{ensure, Context, Result};
+use std::{fs::File, io::BufWriter, time::Instant};
+
+use gol_rs::{
+    cycle::{Cycle, Detector, OnCycle},
+    stats::Writer,
+    Simulation,
+};
+
+use crate::{args::Args, mk_pool};
+
+/// Run `simulation` on the current thread without a terminal until
+/// `--generations` have passed or a cycle stops it, writing stats along
+/// the way. Returns the cycle found, if any, and the simulation as it
+/// ended
+pub fn run(
+    mut simulation: Simulation,
+    args: &Args,
+    threads: usize,
+    mut stats: Option<Writer<BufWriter<File>>>,
+) -> Result<(Option<Cycle>, Simulation)> {
+    ensure!(
+        args.generations.is_some() || args.on_cycle != OnCycle::Continue,
+        "headless runs need --generations or --on-cycle pause or exit to know when to stop"
+    );
+    let started = Instant::now();
+    let target = args.generations.unwrap_or(u64::MAX);
+    let hashlife = simulation.is_hashlife();
+    let (cycle, last) = mk_pool(threads)?.install(|| -> Result<_> {
+        let mut detector = Detector::new(args.history);
+        let mut prev = simulation.world();
+        prev.observe(&mut detector, 0);
+        if let Some(s) = stats.as_mut() {
+            s.write(&prev.sample(None, 0, Default::default()))
+                .context("failed to write stats")?;
+        }
+        let mut cycle = None;
+        while simulation.generation() < target {
+            // hashlife steps stay within the generations left to run
+            let left = target - simulation.generation();
+            simulation.set_step(args.step.min(left.ilog2() as u8));
+            let step_started = Instant::now();
+            let gen = simulation.step();
+            let world = simulation.world();
+            if let Some(s) = stats.as_mut() {
+                let prev = (!hashlife).then_some(&prev);
+                s.write(&world.sample(prev, gen, step_started.elapsed()))
+                    .context("failed to write stats")?;
+            }
+            prev = world;
+            if cycle.is_none() {
+                cycle = prev.observe(&mut detector, gen);
+                if cycle.is_some() && args.on_cycle != OnCycle::Continue {
+                    break;
+                }
+            }
+        }
+        if let Some(s) = stats.as_mut() {
+            s.flush().context("failed to write stats")?;
+        }
+        Ok((cycle, prev))
+    })?;
+    if args.summary {
+        let board = last.board();
+        println!("generation {}", simulation.generation());
+        println!("population {}", board.alive());
+        match board.bounds() {
+            Some((min, max)) => println!("bounds     {} to {}", min, max),
+            None => println!("bounds     none"),
+        }
+        println!("time       {:.3}s", started.elapsed().as_secs_f64());
+    }
+    Ok((cycle, simulation))
+}
@@ -0,0 +1,244 @@
+use std::collections::VecDeque;
+
+use crate::gol::{Board, Diff};
+
+/// Generations between full copies of the board, the rest are diffs
+const KEYFRAME_EVERY: usize = 64;
+
+enum Entry {
+    Keyframe(Board),
+    /// Changes from the board of the previous entry
+    Diff(Diff),
+}
+
+impl Entry {
+    fn bytes(&self) -> usize {
+        match self {
+            Entry::Keyframe(b) => b.bytes(),
+            Entry::Diff(d) => d.bytes(),
+        }
+    }
+}
+
+struct Record {
+    generation: u64,
+    population: u64,
+    entry: Entry,
+}
+
+/// Past boards kept so the frontend can step back through them, stored as
+/// a keyframe every so often with diffs between them. The oldest entries
+/// are dropped a keyframe at a time to stay under a memory budget, which is
+/// only exceeded while a single keyframe and the latest board don't fit in
+/// it on their own
+pub struct History {
+    /// One record per generation, the first one is always a keyframe
+    entries: VecDeque<Record>,
+    latest: Option<Board>,
+    since_keyframe: usize,
+    /// Size of the entries and the latest board
+    bytes: usize,
+    max_bytes: usize,
+}
+
+impl History {
+    pub fn new(max_bytes: usize) -> Self {
+        Self {
+            entries: VecDeque::new(),
+            latest: None,
+            since_keyframe: 0,
+            bytes: 0,
+            max_bytes,
+        }
+    }
+    /// Remember `board` as the board at `generation`, which must be later
+    /// than any recorded so far
+    pub fn record(&mut self, generation: u64, board: &Board) {
+        let diff = self.latest.as_ref().and_then(|prev| prev.diff(board));
+        if let Some(prev) = self.latest.replace(board.clone()) {
+            self.bytes -= prev.bytes();
+        }
+        self.bytes += board.bytes();
+        // once over budget the oldest keyframe can only go if there's
+        // another to rebuild the later boards from, so this one becomes one
+        let over = self.bytes + diff.as_ref().map_or(0, Diff::bytes) > self.max_bytes
+            && !self.has_second_keyframe();
+        let entry = match diff {
+            // a keyframe is cheaper to rebuild from once diffs get large
+            Some(d)
+                if !over
+                    && self.since_keyframe < KEYFRAME_EVERY
+                    && d.bytes() < board.bytes() / 2 =>
+            {
+                self.since_keyframe += 1;
+                Entry::Diff(d)
+            }
+            _ => {
+                self.since_keyframe = 0;
+                Entry::Keyframe(board.clone())
+            }
+        };
+        self.bytes += entry.bytes();
+        self.entries.push_back(Record {
+            generation,
+            population: board.alive() as u64,
+            entry,
+        });
+        while self.bytes > self.max_bytes && self.has_second_keyframe() {
+            self.drop_oldest();
+        }
+    }
+    fn has_second_keyframe(&self) -> bool {
+        self.entries
+            .iter()
+            .skip(1)
+            .any(|r| matches!(r.entry, Entry::Keyframe(_)))
+    }
+    /// Drop the oldest keyframe along with the diffs that depend on it
+    fn drop_oldest(&mut self) {
+        if let Some(r) = self.entries.pop_front() {
+            self.bytes -= r.entry.bytes();
+        }
+        while let Some(Entry::Diff(_)) = self.entries.front().map(|r| &r.entry) {
+            let r = self.entries.pop_front().unwrap();
+            self.bytes -= r.entry.bytes();
+        }
+    }
+
+    /// Oldest and newest generations held
+    pub fn range(&self) -> Option<(u64, u64)> {
+        Some((
+            self.entries.front()?.generation,
+            self.entries.back()?.generation,
+        ))
+    }
+    /// Latest recorded generation before `generation`
+    pub fn before(&self, generation: u64) -> Option<u64> {
+        self.entries
+            .iter()
+            .rev()
+            .map(|r| r.generation)
+            .find(|g| *g < generation)
+    }
+    /// Latest recorded generation at or before `generation`
+    pub fn at_or_before(&self, generation: u64) -> Option<u64> {
+        self.before(generation.saturating_add(1))
+    }
+    /// Earliest recorded generation after `generation`
+    pub fn after(&self, generation: u64) -> Option<u64> {
+        self.entries
+            .iter()
+            .map(|r| r.generation)
+            .find(|g| *g > generation)
+    }
+    /// Generation and population of every record, oldest first
+    pub fn populations(&self) -> Vec<(u64, u64)> {
+        self.entries
+            .iter()
+            .map(|r| (r.generation, r.population))
+            .collect()
+    }
+    /// Board at `generation`, rebuilt from the keyframe before it
+    pub fn get(&self, generation: u64) -> Option<Board> {
+        let end = self
+            .entries
+            .iter()
+            .position(|r| r.generation == generation)?;
+        let start = (0..=end)
+            .rev()
+            .find(|i| matches!(self.entries[*i].entry, Entry::Keyframe(_)))?;
+        let Entry::Keyframe(board) = &self.entries[start].entry else {
+            unreachable!()
+        };
+        let mut board = board.clone();
+        for r in self.entries.range(start + 1..=end) {
+            if let Entry::Diff(d) = &r.entry {
+                board.patch(d);
+            }
+        }
+        Some(board)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gol::Point;
+
+    fn boards(n: i64) -> Vec<Board> {
+        (0..n)
+            .map(|i| {
+                let mut b = Board::empty(100, 100);
+                b.set(Point { x: i, y: i / 3 }, true);
+                b.set(Point { x: 2, y: 2 }, true);
+                b
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_rebuilds_past_boards() {
+        let boards = boards(150);
+        let mut history = History::new(usize::MAX);
+        for (g, b) in boards.iter().enumerate() {
+            history.record(g as u64 * 2, b);
+        }
+        assert_eq!(history.range(), Some((0, 298)));
+        for g in [0, 1, 64, 65, 149] {
+            let board = history.get(g * 2).unwrap();
+            assert_eq!(board.states(), boards[g as usize].states(), "{}", g);
+        }
+        assert!(history.get(3).is_none());
+        assert_eq!(history.before(100), Some(98));
+        assert_eq!(history.after(100), Some(102));
+        assert_eq!(history.after(298), None);
+        assert_eq!(history.at_or_before(101), Some(100));
+        assert_eq!(history.at_or_before(100), Some(100));
+        assert_eq!(history.populations()[3], (6, 2));
+    }
+    #[test]
+    fn test_memory_capped() {
+        let boards = boards(300);
+        let keyframe = boards[0].bytes();
+        let mut history = History::new(keyframe * 3);
+        for (g, b) in boards.iter().enumerate() {
+            history.record(g as u64, b);
+        }
+        assert!(history.bytes <= keyframe * 3);
+        assert_eq!(
+            history.bytes,
+            history
+                .entries
+                .iter()
+                .map(|r| r.entry.bytes())
+                .sum::<usize>()
+                + keyframe
+        );
+        let (first, last) = history.range().unwrap();
+        assert_eq!(last, 299);
+        assert!(first > 0);
+        assert!(matches!(history.entries[0].entry, Entry::Keyframe(_)));
+        assert_eq!(
+            history.get(first).unwrap().states(),
+            boards[first as usize].states()
+        );
+    }
+    #[test]
+    fn test_cap_hit_makes_a_keyframe() {
+        let boards = boards(100);
+        let keyframe = boards[0].bytes();
+        // room for the latest board, one keyframe and a few diffs
+        let mut history = History::new(keyframe * 2 + 64);
+        for (g, b) in boards.iter().enumerate() {
+            history.record(g as u64, b);
+            assert!(history.bytes <= keyframe * 2 + 64, "{}", g);
+        }
+        // a budget too small for a single keyframe only ever holds one
+        let mut history = History::new(keyframe);
+        for (g, b) in boards.iter().enumerate() {
+            history.record(g as u64, b);
+            assert_eq!(history.entries.len(), 1);
+        }
+        assert_eq!(history.range(), Some((99, 99)));
+    }
+}
@@ -0,0 +1,33 @@
+//! Cellular automaton engine behind the `gol-rs` terminal frontend: boards
+//! and rules, the bounded, hashlife and unbounded engines behind
+//! [`Simulation`], pattern readers and [`Frame`] rendering to text
+
+pub mod bgrid;
+pub mod cells;
+pub mod census;
+pub mod cycle;
+pub mod gol;
+pub mod hashlife;
+pub mod history;
+pub mod life;
+pub mod ltl;
+pub mod macrocell;
+pub mod neighborhood;
+pub mod packed;
+pub mod pattern;
+pub mod pbm;
+pub mod pgm;
+pub mod rle;
+pub mod rule;
+pub mod simulation;
+pub mod soup;
+pub mod sparse;
+pub mod stats;
+pub mod timeline;
+pub mod topology;
+pub mod wireworld;
+
+pub use bgrid::Frame;
+pub use gol::{Board, Mask, Point};
+pub use rule::AnyRule;
+pub use simulation::{Generations, Simulation, World};
@@ -0,0 +1,192 @@
+use anyhow::{anyhow, bail, ensure, Context, Result};
+use itertools::Itertools;
+use std::io::{Read, Write};
+
+use crate::{
+    gol::{Board, Point},
+    macrocell::MAX_BOARD,
+    pattern::{Metadata, Pattern},
+};
+
+/// Live cells of a Life 1.05 or 1.06 file, told apart by their `#Life`
+/// line, at the coordinates the file gives them
+pub fn read_points(f: &mut dyn Read) -> Result<(Vec<Point>, Metadata)> {
+    let mut text = String::new();
+    f.read_to_string(&mut text)?;
+    let mut lines = text.lines().map(str::trim);
+    match lines.next() {
+        Some("#Life 1.05") => read_105(lines),
+        Some("#Life 1.06") => read_106(lines),
+        _ => bail!("not a Life 1.05 or 1.06 file"),
+    }
+}
+
+/// Description, name and rule lines shared by both versions, `false` if
+/// `line` is none of them
+fn read_comment(line: &str, meta: &mut Metadata) -> bool {
+    let Some((tag, rest)) = line.split_at_checked(2) else {
+        return false;
+    };
+    let rest = rest.trim().to_string();
+    match tag {
+        "#D" | "#C" => meta.comments.push(rest),
+        "#N" if rest.is_empty() => meta.rule = Some("23/3".to_string()),
+        "#N" => meta.name = Some(rest),
+        "#O" => meta.author = Some(rest),
+        "#R" => meta.rule = Some(rest),
+        _ => return false,
+    }
+    true
+}
+
+/// Blocks of `.` and `*` rows, each placed by the `#P` line before it
+fn read_105<'a>(lines: impl Iterator<Item = &'a str>) -> Result<(Vec<Point>, Metadata)> {
+    let mut meta = Metadata::default();
+    let mut points = Vec::new();
+    let mut corner = Point { x: 0, y: 0 };
+    for line in lines.filter(|l| !l.is_empty()) {
+        if let Some(pos) = line.strip_prefix("#P") {
+            let mut coords = pos.split_whitespace().map(str::parse::<i64>);
+            let (Some(Ok(x)), Some(Ok(y)), None) = (coords.next(), coords.next(), coords.next())
+            else {
+                bail!("invalid block position '{}'", line);
+            };
+            corner = Point { x, y };
+        } else if !read_comment(line, &mut meta) {
+            ensure!(!line.starts_with('#'), "unknown line '{}'", line);
+            for (x, c) in line.chars().enumerate() {
+                match c {
+                    '.' => (),
+                    '*' => points.push(Point {
+                        x: corner.x + x as i64,
+                        y: corner.y,
+                    }),
+                    c => bail!("invalid cell '{}'", c),
+                }
+            }
+            corner.y += 1;
+        }
+    }
+    Ok((points, meta))
+}
+
+/// One `x y` pair per live cell
+fn read_106<'a>(lines: impl Iterator<Item = &'a str>) -> Result<(Vec<Point>, Metadata)> {
+    let mut meta = Metadata::default();
+    let mut points = Vec::new();
+    for line in lines.filter(|l| !l.is_empty()) {
+        if read_comment(line, &mut meta) {
+            continue;
+        }
+        let (x, y) = line
+            .split_once(char::is_whitespace)
+            .ok_or_else(|| anyhow!("expected a coordinate pair, got '{}'", line))?;
+        let coord = |c: &str| {
+            c.trim()
+                .parse::<i64>()
+                .with_context(|| format!("invalid coordinate pair '{}'", line))
+        };
+        points.push(Point {
+            x: coord(x)?,
+            y: coord(y)?,
+        });
+    }
+    Ok((points, meta))
+}
+
+/// Read a Life 1.05 or 1.06 file onto a board just large enough to hold
+/// its cells
+pub fn read(f: &mut dyn Read) -> Result<Pattern> {
+    let (points, meta) = read_points(f)?;
+    let (Some((x0, x1)), Some((y0, y1))) = (
+        points.iter().map(|p| p.x).minmax().into_option(),
+        points.iter().map(|p| p.y).minmax().into_option(),
+    ) else {
+        bail!("empty pattern");
+    };
+    let (width, height) = (
+        x1.abs_diff(x0).saturating_add(1),
+        y1.abs_diff(y0).saturating_add(1),
+    );
+    ensure!(
+        width.saturating_mul(height) <= MAX_BOARD,
+        "the pattern is {}x{}, too large for a board",
+        width,
+        height
+    );
+    let mut board = Board::empty(width as u32, height as u32);
+    for p in points {
+        board.set(
+            Point {
+                x: p.x - x0,
+                y: p.y - y0,
+            },
+            true,
+        );
+    }
+    Ok(Pattern { board, meta })
+}
+
+/// Write the live cells of `board` as Life 1.06, measured from its top
+/// left corner
+pub fn write(f: &mut dyn Write, board: &Board) -> Result<()> {
+    writeln!(f, "#Life 1.06")?;
+    for (pt, alive) in board.pixels() {
+        if alive {
+            writeln!(f, "{} {}", pt.x, pt.y)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GLIDER: [u8; 9] = [0, 1, 0, 0, 0, 1, 1, 1, 1];
+
+    #[test]
+    fn test_read_105() -> Result<()> {
+        let text = "#Life 1.05\n#D Glider\n#N\n#P -1 -1\n.*\n..*\n***\n";
+        let pattern = read(&mut text.as_bytes())?;
+        assert_eq!(pattern.meta.comments, ["Glider"]);
+        assert_eq!(pattern.meta.rule.as_deref(), Some("23/3"));
+        assert_eq!(pattern.board.states(), GLIDER);
+
+        // blocks are placed apart from each other
+        let text = "#Life 1.05\n#R 23/36\n#P 0 0\n*\n#P 3 1\n**\n";
+        let (points, meta) = read_points(&mut text.as_bytes())?;
+        assert_eq!(meta.rule.as_deref(), Some("23/36"));
+        assert_eq!(points, [(0, 0).into(), (3, 1).into(), (4, 1).into()]);
+        assert!(read(&mut "#Life 1.05\n#P 1\n*\n".as_bytes()).is_err());
+        assert!(read(&mut "#Life 1.05\no\n".as_bytes()).is_err());
+        Ok(())
+    }
+    #[test]
+    fn test_too_large() {
+        let text = "#Life 1.05\n#P 0 0\n*\n#P 1000000 1000000\n*\n";
+        assert!(read(&mut text.as_bytes()).is_err());
+        let text = "#Life 1.06\n-9223372036854775808 0\n9223372036854775807 0\n";
+        assert!(read(&mut text.as_bytes()).is_err());
+    }
+    #[test]
+    fn test_read_106() -> Result<()> {
+        let text = "#Life 1.06\n0 -1\n1 0\n-1 1\n0 1\n1 1\n";
+        let (points, _) = read_points(&mut text.as_bytes())?;
+        assert_eq!(points[0], Point { x: 0, y: -1 });
+        assert_eq!(read(&mut text.as_bytes())?.board.states(), GLIDER);
+        assert!(read(&mut "#Life 1.06\n1\n".as_bytes()).is_err());
+        assert!(read(&mut "#Life 1.06\n".as_bytes()).is_err());
+        assert!(read(&mut "#Life 1.07\n0 0\n".as_bytes()).is_err());
+        Ok(())
+    }
+    #[test]
+    fn test_write_round_trip() -> Result<()> {
+        let board = Board::from_states(3, GLIDER.to_vec());
+        let mut out = Vec::new();
+        write(&mut out, &board)?;
+        assert!(out.starts_with(b"#Life 1.06\n1 0\n2 1\n"));
+        assert_eq!(read(&mut out.as_slice())?.board.states(), GLIDER);
+        Ok(())
+    }
+}
@@ -0,0 +1,374 @@
+use anyhow::{anyhow, ensure, Error, Result};
+use rayon::prelude::*;
+use std::{fmt::Display, ops::RangeInclusive, str::FromStr};
+
+use crate::{
+    gol::{Board, Point},
+    neighborhood::Neighborhood,
+    topology::Topology,
+};
+
+const MAX_RANGE: u32 = 500;
+
+/// Larger than Life rule in Golly's `R5,C0,M1,S34..58,B34..45,NM` notation
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LtlRule {
+    range: u32,
+    states: u8,
+    middle: bool,
+    survive: RangeInclusive<u32>,
+    birth: RangeInclusive<u32>,
+    neighborhood: Neighborhood,
+}
+
+fn parse_interval(s: &str) -> Result<RangeInclusive<u32>> {
+    let (lo, hi) = s
+        .split_once("..")
+        .ok_or_else(|| anyhow!("expected min..max, got '{}'", s))?;
+    let lo: u32 = lo.parse()?;
+    let hi: u32 = hi.parse()?;
+    ensure!(lo <= hi, "empty interval {}..{}", lo, hi);
+    Ok(lo..=hi)
+}
+
+impl LtlRule {
+    pub fn states(&self) -> u8 {
+        self.states
+    }
+    pub fn neighborhood(&self) -> Neighborhood {
+        self.neighborhood
+    }
+    /// Next state for a cell in `state` with `count` live cells in its
+    /// neighborhood (including itself when the middle is counted)
+    pub fn next(&self, state: u8, count: u32) -> u8 {
+        match state {
+            0 => self.birth.contains(&count) as u8,
+            1 if self.survive.contains(&count) => 1,
+            s if s + 1 < self.states => s + 1,
+            _ => 0,
+        }
+    }
+}
+
+impl FromStr for LtlRule {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut range = None;
+        let mut states = 2;
+        let mut middle = false;
+        let mut survive = None;
+        let mut birth = None;
+        let mut neighborhood = Neighborhood::Moore;
+        for part in s.trim().to_ascii_uppercase().split(',') {
+            let (key, val) = part.split_at(part.len().min(1));
+            match key {
+                "R" => range = Some(val.parse::<u32>()?),
+                "C" => {
+                    states = match val.parse::<u8>()? {
+                        0 | 2 => 2,
+                        1 => return Err(anyhow!("C1 is not a valid number of states")),
+                        n => n,
+                    }
+                }
+                "M" => {
+                    middle = match val {
+                        "0" => false,
+                        "1" => true,
+                        _ => return Err(anyhow!("M must be 0 or 1, got '{}'", val)),
+                    }
+                }
+                "S" => survive = Some(parse_interval(val)?),
+                "B" => birth = Some(parse_interval(val)?),
+                "N" => neighborhood = val.parse()?,
+                _ => return Err(anyhow!("unexpected section '{}' in rule '{}'", part, s)),
+            }
+        }
+        let range = range.ok_or_else(|| anyhow!("rule '{}' has no range", s))?;
+        ensure!(
+            (1..=MAX_RANGE).contains(&range),
+            "range {} out of bounds, must be 1-{}",
+            range,
+            MAX_RANGE
+        );
+        let rule = Self {
+            range,
+            states,
+            middle,
+            survive: survive.ok_or_else(|| anyhow!("rule '{}' has no S section", s))?,
+            birth: birth.ok_or_else(|| anyhow!("rule '{}' has no B section", s))?,
+            neighborhood,
+        };
+        let max = rule.neighborhood.size(range) - !middle as u32;
+        ensure!(
+            *rule.survive.end() <= max && *rule.birth.end() <= max,
+            "counts in '{}' exceed the neighborhood size of {}",
+            s,
+            max
+        );
+        Ok(rule)
+    }
+}
+
+impl Display for LtlRule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "R{},C{},M{},S{}..{},B{}..{},N{}",
+            self.range,
+            if self.states == 2 { 0 } else { self.states },
+            self.middle as u8,
+            self.survive.start(),
+            self.survive.end(),
+            self.birth.start(),
+            self.birth.end(),
+            self.neighborhood
+        )
+    }
+}
+
+/// Sum of `len` consecutive entries starting at `start` in a cyclic sequence
+/// with prefix sums `prefix` (which has one more entry than the sequence)
+fn wrapped_sum(prefix: &[u32], start: i64, len: i64) -> u32 {
+    let n = (prefix.len() - 1) as i64;
+    let total = prefix[n as usize];
+    let laps = (len / n) as u32;
+    let rem = len % n;
+    let start = start.rem_euclid(n);
+    let end = start + rem;
+    let partial = if end <= n {
+        prefix[end as usize] - prefix[start as usize]
+    } else {
+        total - prefix[start as usize] + prefix[(end - n) as usize]
+    };
+    laps * total + partial
+}
+
+/// Sum of `len` consecutive entries starting at `start` in a sequence with
+/// prefix sums `prefix`, entries past either end counting as zero
+fn clamped_sum(prefix: &[u32], start: i64, len: i64) -> u32 {
+    let n = (prefix.len() - 1) as i64;
+    let (lo, hi) = (start.clamp(0, n), (start + len).clamp(0, n));
+    prefix[hi as usize] - prefix[lo as usize]
+}
+
+fn prefix_sums(vals: impl Iterator<Item = u32>) -> Vec<u32> {
+    std::iter::once(0)
+        .chain(vals.scan(0, |acc, v| {
+            *acc += v;
+            Some(*acc)
+        }))
+        .collect()
+}
+
+/// Live cells in the neighborhood of every cell, looked up one at a time so
+/// that any topology is honored
+fn counts_by_cell(board: &Board, rule: &LtlRule) -> Vec<u32> {
+    let w = board.width() as usize;
+    let offsets: Vec<Point> = rule.neighborhood.offsets(rule.range).collect();
+    (0..w * board.height() as usize)
+        .into_par_iter()
+        .map(|i| {
+            let pt = Point {
+                x: (i % w) as i64,
+                y: (i / w) as i64,
+            };
+            offsets
+                .iter()
+                .filter(|d| board[pt.clone() + (*d).clone()])
+                .count() as u32
+        })
+        .collect()
+}
+
+/// Live cells in the neighborhood of every cell, built from running sums
+/// along rows (and for Moore neighborhoods also along columns) so the cost
+/// per cell does not grow with the square of the range. The edges are joined
+/// as on a torus when `wrap` is set, and cells past them are dead otherwise
+fn prefix_counts(board: &Board, rule: &LtlRule, wrap: bool) -> Vec<u32> {
+    let (w, h) = (board.width() as usize, board.height() as usize);
+    let sum = if wrap { wrapped_sum } else { clamped_sum };
+    let rows: Vec<Vec<u32>> = board
+        .states()
+        .par_chunks(w)
+        .map(|row| prefix_sums(row.iter().map(|s| (*s == 1) as u32)))
+        .collect();
+    let r = rule.range as i64;
+    let row_sum = |y: i64, x: i64, xs: &RangeInclusive<i64>| {
+        let y = match wrap {
+            true => y.rem_euclid(h as i64),
+            false if (0..h as i64).contains(&y) => y,
+            false => return 0,
+        };
+        sum(&rows[y as usize], x + xs.start(), xs.end() - xs.start() + 1)
+    };
+
+    if rule.neighborhood == Neighborhood::Moore {
+        let window = -r..=r;
+        let horiz: Vec<u32> = (0..h * w)
+            .into_par_iter()
+            .map(|i| row_sum((i / w) as i64, (i % w) as i64, &window))
+            .collect();
+        let cols: Vec<Vec<u32>> = (0..w)
+            .into_par_iter()
+            .map(|x| prefix_sums((0..h).map(|y| horiz[y * w + x])))
+            .collect();
+        (0..h * w)
+            .into_par_iter()
+            .map(|i| sum(&cols[i % w], (i / w) as i64 - r, 2 * r + 1))
+            .collect()
+    } else {
+        let extents: Vec<_> = (-r..=r)
+            .filter_map(|dy| Some((dy, rule.neighborhood.row(rule.range, dy)?)))
+            .collect();
+        (0..h * w)
+            .into_par_iter()
+            .map(|i| {
+                let (x, y) = ((i % w) as i64, (i / w) as i64);
+                extents.iter().map(|(dy, xs)| row_sum(y + dy, x, xs)).sum()
+            })
+            .collect()
+    }
+}
+
+/// Advance `board` by a single generation
+pub fn step(board: &Board, rule: &LtlRule) -> Board {
+    let counts = match board.topology() {
+        Topology::Torus => prefix_counts(board, rule, true),
+        Topology::Plane => prefix_counts(board, rule, false),
+        // twisted edges don't line up with running sums along rows and
+        // columns
+        Topology::Klein { .. } | Topology::CrossSurface | Topology::Sphere => {
+            counts_by_cell(board, rule)
+        }
+    };
+    next_states(board, rule, &board.states(), &counts)
+}
+
+fn next_states(board: &Board, rule: &LtlRule, states: &[u8], counts: &[u32]) -> Board {
+    let next = states
+        .par_iter()
+        .zip(counts.par_iter())
+        .map(|(s, c)| {
+            let c = if rule.middle {
+                *c
+            } else {
+                c - (*s == 1) as u32
+            };
+            rule.next(*s, c)
+        })
+        .collect();
+    Board::from_states(board.width(), next).with_topology(board.topology())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gol::Point;
+
+    #[test]
+    fn test_parse_bosco() -> Result<()> {
+        let bosco: LtlRule = "R5,C0,M1,S34..58,B34..45,NM".parse()?;
+        assert_eq!(bosco.range, 5);
+        assert_eq!(bosco.states(), 2);
+        assert!(bosco.middle);
+        assert_eq!(bosco.survive, 34..=58);
+        assert_eq!(bosco.birth, 34..=45);
+        assert_eq!(bosco.to_string(), "R5,C0,M1,S34..58,B34..45,NM");
+        Ok(())
+    }
+    #[test]
+    fn test_parse_invalid() {
+        for s in [
+            "R0,C0,M1,S1..2,B1..2,NM",
+            "R1,C0,M1,S1..2,NM",
+            "R1,C0,M1,S1..10,B1..2,NM",
+            "R1,C0,M2,S1..2,B1..2,NM",
+            "R1,C0,M1,S3..2,B1..2,NM",
+            "R1,C0,M1,S1..2,B1..2,NX",
+        ] {
+            assert!(s.parse::<LtlRule>().is_err(), "accepted {}", s);
+        }
+    }
+    #[test]
+    fn test_wrapped_sum() {
+        let prefix = prefix_sums([1, 0, 1, 1].into_iter());
+        assert_eq!(wrapped_sum(&prefix, 0, 4), 3);
+        assert_eq!(wrapped_sum(&prefix, 3, 2), 2);
+        assert_eq!(wrapped_sum(&prefix, -1, 3), 2);
+        assert_eq!(wrapped_sum(&prefix, 2, 9), 7);
+        assert_eq!(clamped_sum(&prefix, -1, 3), 1);
+        assert_eq!(clamped_sum(&prefix, 2, 9), 2);
+    }
+
+    fn naive_counts(board: &Board, rule: &LtlRule) -> Vec<u32> {
+        let mut out = Vec::new();
+        for y in 0..board.height() as i64 {
+            for x in 0..board.width() as i64 {
+                out.push(
+                    rule.neighborhood
+                        .offsets(rule.range)
+                        .filter(|d| board[Point { x, y } + d.clone()])
+                        .count() as u32,
+                );
+            }
+        }
+        out
+    }
+
+    #[test]
+    fn test_step_matches_naive() -> Result<()> {
+        let buf = (0..24 * 20).map(|i| (i * 7 + i / 5) % 3 == 0).collect();
+        let board = Board::new(24, buf);
+        for n in ["NM", "NN", "NC", "NH"] {
+            let rule: LtlRule = format!("R3,C0,M1,S10..20,B8..14,{}", n).parse()?;
+            let expected: Vec<u8> = naive_counts(&board, &rule)
+                .into_iter()
+                .zip(board.states())
+                .map(|(c, s)| rule.next(s, c))
+                .collect();
+            assert_eq!(step(&board, &rule).states(), expected, "{}", n);
+        }
+        Ok(())
+    }
+    #[test]
+    fn test_counts_honor_topology() -> Result<()> {
+        let rule: LtlRule = "R1,C0,M1,S3..8,B4..6,NM".parse()?;
+        let board = Board::new(5, vec![true; 25]);
+        assert!(counts_by_cell(&board, &rule).iter().all(|c| *c == 9));
+        // cells past the edges of a plane are dead
+        let counts = counts_by_cell(&board.with_topology(Topology::Plane), &rule);
+        assert_eq!((counts[0], counts[2], counts[12]), (4, 6, 9));
+        Ok(())
+    }
+    #[test]
+    fn test_plane_counts_match_by_cell() -> Result<()> {
+        let buf = (0..24 * 20).map(|i| (i * 7 + i / 5) % 3 == 0).collect();
+        let board = Board::new(24, buf).with_topology(Topology::Plane);
+        for rule in ["R3,C0,M1,S10..20,B8..14", "R12,C0,M1,S10..20,B8..14"] {
+            for n in ["NM", "NN", "NC", "NH"] {
+                let rule: LtlRule = format!("{},{}", rule, n).parse()?;
+                assert_eq!(
+                    prefix_counts(&board, &rule, false),
+                    counts_by_cell(&board, &rule),
+                    "{}",
+                    rule
+                );
+            }
+        }
+        Ok(())
+    }
+    #[test]
+    fn test_range_one_is_life() -> Result<()> {
+        let rule: LtlRule = "R1,C0,M0,S2..3,B3..3,NM".parse()?;
+        let mut board = Board::new(5, vec![false; 25]);
+        for x in 1..4 {
+            board.set(Point { x, y: 2 }, true);
+        }
+        let next = step(&board, &rule);
+        assert!(next[Point { x: 2, y: 1 }] && next[Point { x: 2, y: 3 }]);
+        assert!(!next[Point { x: 1, y: 2 }]);
+        assert_eq!(next.alive(), 3);
+        Ok(())
+    }
+}
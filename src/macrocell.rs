@@ -0,0 +1,457 @@
+use anyhow::{anyhow, bail, ensure, Context, Result};
+use std::{
+    collections::HashMap,
+    io::{Read, Write},
+};
+
+use crate::{
+    gol::{Board, Point},
+    pattern::Metadata,
+    rule::AnyRule,
+};
+
+/// Level of the leaves, which are 8x8 bitmaps
+pub const LEAF: u8 = 3;
+
+/// Largest level read, so every cell still has an `i64` coordinate
+const MAX_LEVEL: u8 = 60;
+
+/// Most cells a board read from a pattern file may have, larger macrocell
+/// patterns are left to hashlife
+pub(crate) const MAX_BOARD: u64 = 1 << 28;
+
+/// Node of the quadtree, quadrants are referred to by id with 0 standing
+/// for an empty one
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub(crate) enum Node {
+    /// Bit `y * 8 + x` is the cell at `(x, y)`
+    Leaf(u64),
+    /// North west, north east, south west and south east quadrants of a
+    /// node `2^level` cells across
+    Branch { level: u8, quads: [usize; 4] },
+}
+
+/// Pattern as a quadtree in which equal subtrees are stored once, the form
+/// macrocell files take. The root is centred on the origin
+#[derive(Clone, Debug)]
+pub struct Macrocell {
+    /// Node `id` is at `id - 1`, children always come before their parents
+    nodes: Vec<Node>,
+    index: HashMap<Node, usize>,
+    root: usize,
+    level: u8,
+    pub meta: Metadata,
+    /// Generation the pattern was saved at
+    pub generation: u64,
+}
+
+impl Default for Macrocell {
+    fn default() -> Self {
+        Self {
+            nodes: Vec::new(),
+            index: HashMap::new(),
+            root: 0,
+            level: LEAF,
+            meta: Metadata::default(),
+            generation: 0,
+        }
+    }
+}
+
+impl Macrocell {
+    /// Id of `node`, adding it unless an equal one is already there
+    pub(crate) fn push(&mut self, node: Node) -> usize {
+        if matches!(
+            node,
+            Node::Leaf(0)
+                | Node::Branch {
+                    quads: [0, 0, 0, 0],
+                    ..
+                }
+        ) {
+            return 0;
+        }
+        if let Some(id) = self.index.get(&node) {
+            return *id;
+        }
+        self.nodes.push(node);
+        self.index.insert(node, self.nodes.len());
+        self.nodes.len()
+    }
+    pub(crate) fn node(&self, id: usize) -> Option<Node> {
+        id.checked_sub(1).map(|i| self.nodes[i])
+    }
+    pub(crate) fn root(&self) -> usize {
+        self.root
+    }
+    /// Make `id`, a node `2^level` cells across, the root
+    pub(crate) fn set_root(&mut self, id: usize, level: u8) {
+        self.root = id;
+        self.level = level;
+    }
+    /// Cells along each side of the root, as a power of two
+    pub fn level(&self) -> u8 {
+        self.level
+    }
+
+    /// Copy of `board` with its top left corner at the root's
+    pub fn from_board(board: &Board) -> Self {
+        let mut mc = Self::default();
+        let size = board.width().max(board.height()) as u64;
+        let mut level = LEAF;
+        while 1 << level < size {
+            level += 1;
+        }
+        let root = mc.build(board, level, Point { x: 0, y: 0 });
+        mc.set_root(root, level);
+        mc
+    }
+    fn build(&mut self, board: &Board, level: u8, corner: Point) -> usize {
+        let size = 1i64 << level;
+        if corner.x >= board.width() as i64 || corner.y >= board.height() as i64 {
+            return 0;
+        }
+        if level == LEAF {
+            let mut bits = 0;
+            for i in 0..64 {
+                let pt = corner.clone() + Point { x: i % 8, y: i / 8 };
+                if pt.x < board.width() as i64 && pt.y < board.height() as i64 && board[pt] {
+                    bits |= 1 << i;
+                }
+            }
+            return self.push(Node::Leaf(bits));
+        }
+        let half = size / 2;
+        let quads = [(0, 0), (half, 0), (0, half), (half, half)]
+            .map(|(x, y)| self.build(board, level - 1, corner.clone() + Point { x, y }));
+        self.push(Node::Branch { level, quads })
+    }
+
+    /// Top left and bottom right live cells, `None` if there are none
+    pub fn bounds(&self) -> Option<(Point, Point)> {
+        let mut memo = HashMap::new();
+        let (min, max) = self.node_bounds(self.root, &mut memo)?;
+        let half = 1i64 << (self.level - 1);
+        let origin = Point { x: -half, y: -half };
+        Some((origin.clone() + min, origin + max))
+    }
+    /// Bounds of the live cells of `id` measured from its top left corner
+    fn node_bounds(
+        &self,
+        id: usize,
+        memo: &mut HashMap<usize, Option<(Point, Point)>>,
+    ) -> Option<(Point, Point)> {
+        if let Some(b) = memo.get(&id) {
+            return b.clone();
+        }
+        let bounds = match self.node(id)? {
+            Node::Leaf(bits) => {
+                let cells = (0..64).filter(|i| bits & 1 << i != 0);
+                let (xs, ys): (Vec<i64>, Vec<i64>) = cells.map(|i| (i % 8, i / 8)).unzip();
+                Some((
+                    Point {
+                        x: *xs.iter().min()?,
+                        y: *ys.iter().min()?,
+                    },
+                    Point {
+                        x: *xs.iter().max()?,
+                        y: *ys.iter().max()?,
+                    },
+                ))
+            }
+            Node::Branch { level, quads } => {
+                let half = 1i64 << (level - 1);
+                quads
+                    .into_iter()
+                    .zip([(0, 0), (half, 0), (0, half), (half, half)])
+                    .filter_map(|(q, (x, y))| {
+                        let (min, max) = self.node_bounds(q, memo)?;
+                        let offset = Point { x, y };
+                        Some((min + offset.clone(), max + offset))
+                    })
+                    .reduce(|(a, b), (c, d)| {
+                        (
+                            Point {
+                                x: a.x.min(c.x),
+                                y: a.y.min(c.y),
+                            },
+                            Point {
+                                x: b.x.max(d.x),
+                                y: b.y.max(d.y),
+                            },
+                        )
+                    })
+            }
+        };
+        memo.insert(id, bounds.clone());
+        bounds
+    }
+    /// Whether [`Macrocell::to_board`] can fit the live cells on a board
+    pub fn fits_board(&self) -> bool {
+        self.bounds().is_none_or(|(min, max)| {
+            let (w, h) = ((max.x - min.x + 1) as u64, (max.y - min.y + 1) as u64);
+            w.saturating_mul(h) <= MAX_BOARD
+        })
+    }
+    /// Board just large enough to hold the live cells
+    pub fn to_board(&self) -> Result<Board> {
+        let (min, max) = self.bounds().ok_or_else(|| anyhow!("empty pattern"))?;
+        let (w, h) = ((max.x - min.x + 1) as u64, (max.y - min.y + 1) as u64);
+        ensure!(
+            self.fits_board(),
+            "the pattern is {}x{}, too large for a board, run it with --hashlife",
+            w,
+            h
+        );
+        let mut board = Board::empty(w as u32, h as u32);
+        let half = 1i64 << (self.level - 1);
+        let corner = Point {
+            x: -half - min.x,
+            y: -half - min.y,
+        };
+        self.fill(&mut board, self.root, corner);
+        Ok(board)
+    }
+    fn fill(&self, board: &mut Board, id: usize, corner: Point) {
+        match self.node(id) {
+            None => (),
+            Some(Node::Leaf(bits)) => {
+                for i in (0..64).filter(|i| bits & 1 << i != 0) {
+                    board.set(corner.clone() + Point { x: i % 8, y: i / 8 }, true);
+                }
+            }
+            Some(Node::Branch { level, quads }) => {
+                let size = 1i64 << level;
+                let (w, h) = (board.width() as i64, board.height() as i64);
+                if corner.x >= w || corner.y >= h || corner.x + size <= 0 || corner.y + size <= 0 {
+                    return;
+                }
+                let half = size / 2;
+                for (q, (x, y)) in
+                    quads
+                        .into_iter()
+                        .zip([(0, 0), (half, 0), (0, half), (half, half)])
+                {
+                    self.fill(board, q, corner.clone() + Point { x, y });
+                }
+            }
+        }
+    }
+}
+
+/// Bitmap of a leaf line, rows of `.` and `*` each ended by a `$`
+fn read_leaf(line: &str) -> Result<u64> {
+    let mut bits = 0;
+    let (mut x, mut y) = (0, 0);
+    for c in line.chars() {
+        match c {
+            '$' => (x, y) = (0, y + 1),
+            '.' | '*' => {
+                ensure!(x < 8 && y < 8, "leaf '{}' is larger than 8x8", line);
+                if c == '*' {
+                    bits |= 1 << (y * 8 + x);
+                }
+                x += 1;
+            }
+            c => bail!("invalid cell '{}' in leaf", c),
+        }
+    }
+    Ok(bits)
+}
+
+/// Read a two-state macrocell file
+pub fn read(f: &mut dyn Read) -> Result<Macrocell> {
+    let mut text = String::new();
+    f.read_to_string(&mut text)?;
+    let mut lines = text.lines().map(str::trim);
+    ensure!(
+        lines.next().is_some_and(|l| l.starts_with("[M2]")),
+        "not a macrocell file"
+    );
+    let mut mc = Macrocell::default();
+    // ids as numbered in the file, which `push` may have merged
+    let mut ids = vec![0];
+    let mut levels = vec![0];
+    for line in lines.filter(|l| !l.is_empty()) {
+        if let Some(comment) = line.strip_prefix('#') {
+            let (tag, rest) = comment.split_at_checked(1).unwrap_or((comment, ""));
+            let rest = rest.trim().to_string();
+            match tag {
+                "R" => mc.meta.rule = Some(rest),
+                "G" => mc.generation = rest.parse().context("invalid generation")?,
+                "N" => mc.meta.name = Some(rest),
+                "O" => mc.meta.author = Some(rest),
+                "C" | "D" => mc.meta.comments.push(rest),
+                _ => (),
+            }
+            continue;
+        }
+        let (id, level) = if line.starts_with(['.', '*', '$']) {
+            (mc.push(Node::Leaf(read_leaf(line)?)), LEAF)
+        } else {
+            let fields = line
+                .split_whitespace()
+                .map(str::parse::<usize>)
+                .collect::<Result<Vec<_>, _>>()
+                .with_context(|| format!("invalid node '{}'", line))?;
+            let [level, a, b, c, d] = fields[..] else {
+                bail!("invalid node '{}'", line);
+            };
+            let quads = [a, b, c, d];
+            ensure!(level != 1, "multi-state macrocell files aren't supported");
+            ensure!(
+                (LEAF as usize + 1..=MAX_LEVEL as usize).contains(&level),
+                "invalid node level {}",
+                level
+            );
+            let level = level as u8;
+            let mut mapped = [0; 4];
+            for (m, q) in mapped.iter_mut().zip(quads) {
+                ensure!(
+                    q < ids.len(),
+                    "node {} refers to node {} after it",
+                    ids.len(),
+                    q
+                );
+                ensure!(
+                    q == 0 || levels[q] == level - 1,
+                    "node {} of level {} has a quadrant of level {}",
+                    ids.len(),
+                    level,
+                    levels[q]
+                );
+                *m = ids[q];
+            }
+            let node = Node::Branch {
+                level,
+                quads: mapped,
+            };
+            (mc.push(node), level)
+        };
+        ids.push(id);
+        levels.push(level);
+        mc.set_root(id, level);
+    }
+    Ok(mc)
+}
+
+/// Leaf line for `bits`, trailing dead cells and rows are left out
+fn write_leaf(bits: u64) -> String {
+    let rows: Vec<String> = (0..8)
+        .map(|y| {
+            let row: String = (0..8)
+                .map(|x| {
+                    if bits & 1 << (y * 8 + x) != 0 {
+                        '*'
+                    } else {
+                        '.'
+                    }
+                })
+                .collect();
+            row.trim_end_matches('.').to_string()
+        })
+        .collect();
+    let last = rows
+        .iter()
+        .rposition(|r| !r.is_empty())
+        .map_or(0, |l| l + 1);
+    rows[..last].iter().map(|r| format!("{}$", r)).collect()
+}
+
+/// Write `mc` as a macrocell file for `rule`
+pub fn write(f: &mut dyn Write, mc: &Macrocell, rule: &AnyRule) -> Result<()> {
+    writeln!(f, "[M2] (gol-rs)")?;
+    writeln!(f, "#R {}", rule)?;
+    if mc.generation > 0 {
+        writeln!(f, "#G {}", mc.generation)?;
+    }
+    // only the nodes reachable from the root, renumbered in order
+    let mut ids = HashMap::from([(0, 0)]);
+    let mut lines = Vec::new();
+    write_node(mc, mc.root, &mut ids, &mut lines);
+    if lines.is_empty() {
+        // an empty pattern still needs a root
+        lines.push(format!("{} 0 0 0 0", LEAF + 1));
+    }
+    for line in lines {
+        writeln!(f, "{}", line)?;
+    }
+    Ok(())
+}
+fn write_node(
+    mc: &Macrocell,
+    id: usize,
+    ids: &mut HashMap<usize, usize>,
+    lines: &mut Vec<String>,
+) -> usize {
+    if let Some(n) = ids.get(&id) {
+        return *n;
+    }
+    let line = match mc.node(id).expect("id 0 is always mapped") {
+        Node::Leaf(bits) => write_leaf(bits),
+        Node::Branch { level, quads } => {
+            let [a, b, c, d] = quads.map(|q| write_node(mc, q, ids, lines));
+            format!("{} {} {} {} {}", level, a, b, c, d)
+        }
+    };
+    lines.push(line);
+    ids.insert(id, lines.len());
+    lines.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GLIDER: &str = "[M2] (golly 2.0)\n#R B3/S23\n#G 4\n.*$..*$***$\n4 0 0 0 1\n";
+
+    #[test]
+    fn test_read() -> Result<()> {
+        let mc = read(&mut GLIDER.as_bytes())?;
+        assert_eq!(mc.meta.rule.as_deref(), Some("B3/S23"));
+        assert_eq!((mc.generation, mc.level()), (4, 4));
+        // the leaf is the south east quadrant, just past the origin
+        let bounds = mc.bounds();
+        assert_eq!(bounds, Some(((0, 0).into(), (2, 2).into())));
+        assert_eq!(mc.to_board()?.states(), [0, 1, 0, 0, 0, 1, 1, 1, 1]);
+
+        assert!(read(&mut "[M2]\n4 0 0 0 1\n".as_bytes()).is_err());
+        assert!(read(&mut "[M2]\n*$\n5 0 0 0 1\n".as_bytes()).is_err());
+        assert!(read(&mut "[M2]\n*********$\n".as_bytes()).is_err());
+        assert!(read(&mut "[M2]\n1 0 0 0 1\n".as_bytes()).is_err());
+        assert!(read(&mut "*$\n".as_bytes()).is_err());
+        Ok(())
+    }
+    #[test]
+    fn test_shared_nodes() -> Result<()> {
+        // four copies of the same leaf, then four copies of that
+        let text = "[M2]\n*$\n4 1 1 1 1\n5 2 2 2 2\n";
+        let mc = read(&mut text.as_bytes())?;
+        assert_eq!(mc.nodes.len(), 3);
+        let board = mc.to_board()?;
+        assert_eq!((board.width(), board.alive()), (25, 16));
+        Ok(())
+    }
+    #[test]
+    fn test_write_round_trip() -> Result<()> {
+        let board = Board::from_states(3, vec![0, 1, 0, 0, 0, 1, 1, 1, 1]);
+        let mc = Macrocell::from_board(&board);
+        let mut out = Vec::new();
+        write(&mut out, &mc, &AnyRule::default())?;
+        let text = String::from_utf8(out)?;
+        assert_eq!(text, "[M2] (gol-rs)\n#R B3/S23\n.*$..*$***$\n");
+        assert_eq!(
+            read(&mut text.as_bytes())?.to_board()?.states(),
+            board.states()
+        );
+
+        let mc = read(&mut GLIDER.as_bytes())?;
+        let mut out = Vec::new();
+        write(&mut out, &mc, &AnyRule::default())?;
+        assert_eq!(
+            String::from_utf8(out)?,
+            GLIDER.replace("golly 2.0", "gol-rs")
+        );
+        Ok(())
+    }
+}
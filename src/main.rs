@@ -1,269 +1,199 @@
 use std::any::Any;
-use std::io::{BufReader, Read};
-use std::ops::Deref;
-use std::panic::PanicInfo;
-use std::sync::atomic::AtomicBool;
-use std::sync::mpsc::Receiver;
+use std::fs::File;
+use std::io::BufWriter;
+use std::panic;
+use std::panic::PanicHookInfo;
 use std::sync::{Arc, Mutex};
-use std::thread::sleep;
-use std::{panic, sync};
 
-use anyhow::{anyhow, ensure, Result};
+use anyhow::{bail, ensure, Context, Result};
 use args::Args;
-use bgrid::{Charset, Frame};
 use clap::Parser;
-use gol::{Mask, Point};
-use pancurses::{curs_set, endwin, init_pair, noecho, start_color, Input};
-use rayon::prelude::*;
-use rayon::slice::ParallelSliceMut;
-use scopeguard::defer;
-use std::time::Duration;
+use gol_rs::census::Census;
+use gol_rs::cycle::OnCycle;
+use gol_rs::pattern::{Format, Metadata, Pattern};
+use gol_rs::rule::AnyRule;
+use gol_rs::{pattern, pgm, soup, stats, Simulation};
+
+#[cfg(all(feature = "tui", not(any(feature = "curses", feature = "crossterm"))))]
+compile_error!("the tui feature needs the curses or crossterm backend");
 
 mod args;
-mod bgrid;
-mod gol;
+mod headless;
+#[cfg(feature = "tui")]
+mod tui;
 
-type Board = gol::Board;
+/// Stand in for the terminal interface when built without it
+#[cfg(not(feature = "tui"))]
+mod tui {
+    use anyhow::{bail, Result};
+    use gol_rs::{cycle::Cycle, stats::Writer, Simulation};
+    use std::{fs::File, io::BufWriter};
 
-fn calc_px(board: &Board, pt: &Point) -> bool {
-    let alive = board.neighbors(pt).iter().filter(|p| **p).count();
-    let imalive = board[pt.clone()];
-    if !imalive && alive == 3 {
-        return true;
-    } else if imalive && (alive < 2 || alive > 3) {
-        return false;
-    } else {
-        return imalive;
+    use crate::args::Args;
+
+    pub fn run(
+        _: Simulation,
+        _: &Args,
+        _: u16,
+        _: Option<Writer<BufWriter<File>>>,
+    ) -> Result<(Option<Cycle>, Simulation)> {
+        bail!("built without the tui feature, only --headless runs are available")
     }
 }
+
+/// Largest window onto a macrocell pattern run with hashlife, unless
+/// --size asks for another
+const MAX_WINDOW: u32 = 4096;
+
 fn mk_pool(threads: usize) -> Result<rayon::ThreadPool> {
     Ok(rayon::ThreadPoolBuilder::new()
         .num_threads(threads)
         .build()?)
 }
 
-fn run_turn(board: Board, threads: u32) -> Result<Board> {
-    let chunk_size = ((board.height() as f32) / threads as f32).ceil() as usize;
-    let mut outboard = board.clone();
-    outboard
-        .pixels_mut()
-        .par_chunks_mut(chunk_size)
-        .for_each(|cs| {
-            for (pt, v) in cs {
-                //dbg!(*c.1, calc_px(&board, &c.0));
-                **v = calc_px(&board, &pt);
-            }
-        });
-    Ok(outboard)
-}
-
-fn read_pgm(f: &mut dyn Read) -> Result<Board> {
-    let buf = BufReader::new(f);
-    let bytes: Vec<u8> = buf
-        .bytes()
-        .map(|m| m.map_err(|e| anyhow!(e)))
-        .collect::<Result<_>>()?;
-    let (sections, pixels): (Vec<_>, Vec<_>) = bytes
-        .split(|bv| {
-            let b = *bv as char;
-            b == ' ' || b == '\n' || b == '\r' || b == '\t'
-        })
-        .enumerate()
-        .partition(|(i, _)| *i < 4);
-    let sections: Vec<String> = sections
-        .into_iter()
-        .map(|(_, cs)| -> Result<String> {
-            Ok(String::from_utf8(cs.iter().map(|c| *c as u8).collect())?)
-        })
-        .collect::<Result<_>>()?;
-    let pixels: Vec<_> = pixels.into_iter().flat_map(|m| m.1).collect();
-    ensure!(sections.len() == 4, "invalid number of sections");
-    ensure!(sections[0] == "P5", "not a P5 pgm");
-    let width: u32 = sections[1].parse()?;
-    let maxgrey: u16 = sections[3].parse()?;
-    ensure!(maxgrey < 256, "max grey too high!");
-    Ok(Board::new(
-        width,
-        pixels
-            .into_iter()
-            .map(|p| *p as u8 == maxgrey as u8)
-            .collect(),
-    ))
-}
-enum Event {
-    TurnEnd(Board),
-    KeyPress(Input),
+/// Pattern the run starts from, a random soup or the input pattern padded
+/// and with its edges joined as asked
+fn initial_pattern(args: &Args, seed: u64) -> Result<Pattern> {
+    let Pattern {
+        board: initial,
+        meta,
+    } = match (&args.input, args.random) {
+        (_, Some(size)) => soup::generate(size, args.density, args.symmetry, seed)?.into(),
+        (Some(input), None) => {
+            let threshold = pgm::Threshold {
+                level: args.threshold,
+                invert: args.invert,
+            };
+            pattern::load(input, threshold)?
+        }
+        (None, None) => bail!("either an input file or --random is needed"),
+    };
+    let initial = match args.size {
+        Some(size) => {
+            ensure!(
+                size.width >= initial.width() && size.height >= initial.height(),
+                "the pattern is {}x{}, larger than --size {}",
+                initial.width(),
+                initial.height(),
+                size
+            );
+            initial.resized(size.width, size.height)
+        }
+        None => initial,
+    };
+    Ok(Pattern {
+        board: args.topology.apply(initial)?,
+        meta,
+    })
 }
 
-struct SessionWin {
-    win: pancurses::Window,
+/// Rule to run, --rule over the one the pattern file names and Life if
+/// neither gives one
+fn pick_rule(args: &Args, meta: &Metadata) -> Result<AnyRule> {
+    match (&args.rule, &meta.rule) {
+        (rule, None) => Ok(rule.clone().unwrap_or_default()),
+        (None, Some(file_rule)) => file_rule
+            .parse()
+            .with_context(|| format!("unsupported rule {} in the pattern file", file_rule)),
+        (Some(rule), Some(file_rule)) => {
+            if file_rule.parse::<AnyRule>().ok().as_ref() != Some(rule) {
+                eprintln!(
+                    "warning: --rule {} overrides the pattern's rule {}",
+                    rule, file_rule
+                );
+            }
+            Ok(rule.clone())
+        }
+    }
 }
-impl Deref for SessionWin {
-    type Target = pancurses::Window;
 
-    fn deref(&self) -> &Self::Target {
-        return &self.win;
+fn run_game() -> Result<()> {
+    let args = Args::parse();
+    let threads = args
+        .threads
+        .unwrap_or_else(|| num_cpus::get().saturating_sub(2).max(1) as u16);
+    let seed = args.seed.unwrap_or_else(|| {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |d| d.as_nanos() as u64)
+    });
+    let random = args.random.is_some() && args.seed.is_none();
+    // macrocell patterns go straight into hashlife, they needn't fit on a
+    // board that way
+    let tree = match &args.input {
+        Some(input) if args.hashlife => pattern::load_macrocell(input)?,
+        _ => None,
+    };
+    let simulation = if let Some(mc) = tree {
+        let rule = pick_rule(&args, &mc.meta)?;
+        let (width, height) = match (args.size, mc.bounds()) {
+            (Some(size), _) => (size.width, size.height),
+            (None, Some((min, max))) => (
+                ((max.x - min.x + 1) as u32).min(MAX_WINDOW),
+                ((max.y - min.y + 1) as u32).min(MAX_WINDOW),
+            ),
+            (None, None) => (MAX_WINDOW, MAX_WINDOW),
+        };
+        Simulation::from_macrocell(&mc, rule, width, height)?
+    } else {
+        let Pattern { board, meta } = initial_pattern(&args, seed)?;
+        let rule = pick_rule(&args, &meta)?;
+        if args.hashlife {
+            Simulation::hashlife(&board, rule)?
+        } else if args.unbounded {
+            Simulation::unbounded(&board, rule)?
+        } else {
+            Simulation::new(board, rule)
+        }
+    };
+    let rule = simulation.rule().clone();
+    match (args.census, &rule) {
+        (true, AnyRule::Life(r)) => Census::check(r, args.topology.topology)?,
+        (true, _) => bail!("the census needs a B/S rule"),
+        (false, _) => (),
     }
-}
-impl Drop for SessionWin {
-    fn drop(&mut self) {
-        endwin();
+    let stats = match &args.stats_out {
+        Some(path) => Some(stats::Writer::new(
+            BufWriter::new(
+                File::create(path)
+                    .with_context(|| format!("failed to create {}", path.display()))?,
+            ),
+            stats::Format::from_path(path),
+        )),
+        None => None,
+    };
+    let (cycle, simulation) = if args.headless {
+        headless::run(simulation, &args, threads as usize, stats)?
+    } else {
+        tui::run(simulation, &args, threads, stats)?
+    };
+    if let Some(cycle) = cycle.filter(|_| args.on_cycle != OnCycle::Continue) {
+        println!("stabilized with {}", cycle);
     }
-}
-impl SessionWin {
-    fn initscr() -> Self {
-        let win = pancurses::initscr();
-        start_color();
-        Self { win }
+    if random {
+        println!("seed {}", seed);
     }
-}
-
-fn check(r: i32) -> Result<()> {
-    if r != 0 {
-        Err(anyhow!("pancurses returned error code {}", r))
-    } else {
-        Ok(())
+    let last = simulation.world();
+    if let (true, AnyRule::Life(r)) = (args.census, &rule) {
+        print!("{}", Census::take(&last.board(), r)?);
     }
-}
-fn run_event_loop(
-    running: &AtomicBool,
-    tx: Receiver<Event>,
-    bg: char,
-    chset: Charset,
-) -> Result<()> {
-    let win = SessionWin::initscr();
-    win.keypad(true);
-    win.nodelay(true);
-    win.clear();
-    win.refresh();
-    curs_set(0);
-    noecho();
-
-    defer! { running.store(false, sync::atomic::Ordering::SeqCst); }
-    start_color();
-    init_pair(0, pancurses::COLOR_WHITE, pancurses::COLOR_BLACK);
-    init_pair(3, pancurses::COLOR_GREEN, pancurses::COLOR_BLACK);
-
-    let scroll_inc: i64 =
-        (win.get_max_x().max(win.get_max_y()) / 20 / chset.scale().1 as i32).into();
-    let mut offset = Point { x: 0, y: 0 };
-    let mut turn = 0;
-    while running.load(sync::atomic::Ordering::SeqCst) {
-        let mut ev = Option::None;
-        while let None = ev {
-            ev = win
-                .getch()
-                .map(|c| Event::KeyPress(c))
-                .or_else(|| tx.try_recv().ok());
-            if ev.is_none() {
-                sleep(Duration::from_millis(1));
-            }
+    match &args.output {
+        // a hashlife universe is saved whole rather than just the window
+        Some(path) if Format::from_path(path) == Some(Format::Macrocell) => {
+            pattern::save_macrocell(path, &simulation.macrocell(), &rule)?
         }
-        let ev = ev.unwrap();
-        match ev {
-            Event::TurnEnd(b) => {
-                turn += 1;
-                offset.remap(b.width(), b.height());
-                let viewport = Mask {
-                    x: (offset.x) as u32,
-                    y: (offset.y) as u32,
-                    w: (win.get_max_x()) as u32,
-                    h: (win.get_max_y()) as u32,
-                };
-                let frame = Frame::new(b.clone(), viewport);
-                let screen_view = Mask {
-                    x: win.get_beg_x() as u32,
-                    y: win.get_beg_y() as u32,
-                    w: win.get_max_x() as u32,
-                    h: win.get_max_y() as u32,
-                };
-                win.color_set(0);
-                win.clear();
-                frame
-                    .render(bg, chset)
-                    .into_iter()
-                    .map(|(pt, c)| {
-                        if !screen_view.contains(&pt) {
-                            Err(anyhow!(
-                                "tried to draw outside the viewport: {} not in {}",
-                                pt,
-                                screen_view
-                            ))
-                        } else {
-                            let r = check(win.mvaddstr(pt.y as i32, pt.x as i32, String::from(c)))
-                                .map_err(|e| {
-                                    anyhow!("check failed pt {} v: {} e: {}", pt, screen_view, e)
-                                });
-                            if r.is_ok()
-                                || pt.x == (win.get_max_x() - 1) as i64
-                                    && pt.y == (win.get_max_y() - 1) as i64
-                            {
-                                Ok(())
-                            } else {
-                                r
-                            }
-                        }
-                    })
-                    .collect::<Result<_>>()?;
-                win.color_set(3);
-                win.mvaddstr(0, 0, format!("turn   {}", turn));
-                win.mvaddstr(1, 0, format!("alive  {}", b.alive()));
-                win.mvaddstr(2, 0, format!("offset {}", offset));
-                win.refresh();
-            }
-            Event::KeyPress(Input::KeyLeft) | Event::KeyPress(Input::Character('h')) => {
-                offset.x -= scroll_inc
-            }
-            Event::KeyPress(Input::KeyRight) | Event::KeyPress(Input::Character('l')) => {
-                offset.x += scroll_inc
-            }
-            Event::KeyPress(Input::KeyUp) | Event::KeyPress(Input::Character('k')) => {
-                offset.y -= scroll_inc
-            }
-            Event::KeyPress(Input::KeyDown) | Event::KeyPress(Input::Character('j')) => {
-                offset.y += scroll_inc
-            }
-            Event::KeyPress(Input::KeyEIC) | Event::KeyPress(Input::Character('q')) => {
-                running.store(false, sync::atomic::Ordering::SeqCst);
-            }
-            _ => (),
+        Some(path) if args.plain && Format::from_path(path) == Some(Format::Pbm) => {
+            pattern::save_as(path, Format::PlainPbm, &last.board(), &rule)?
         }
+        Some(path) => pattern::save(path, &last.board(), &rule)?,
+        None => (),
     }
     Ok(())
 }
-fn run_game() -> Result<()> {
-    let args = Args::parse();
-    let threads = args.threads.unwrap_or_else(|| (num_cpus::get() - 2) as u16);
-    let mut infile = std::fs::File::open(args.input)?;
-    let initial = read_pgm(&mut infile)?;
-    let (sx, tx) = std::sync::mpsc::channel();
-    let bsx = sx.clone();
-    let running = AtomicBool::new(true);
-
-    let mut curr = initial.clone();
-    let running = &running;
-    std::thread::scope(move |s| {
-        s.spawn(move || {
-            mk_pool(threads as usize)
-                .expect("failed to create threadpool")
-                .install(move || {
-                    while running.load(sync::atomic::Ordering::SeqCst) {
-                        curr = run_turn(curr, threads as u32).expect("failed to run turn");
-                        let r = bsx.send(Event::TurnEnd(curr.clone()));
-                        if r.is_err() {
-                            break;
-                        }
-                    }
-                })
-        });
-
-        run_event_loop(&running, tx, args.background, args.charset)
-    })
-}
 fn with_handler<H, F, R>(handler: H, func: F) -> Result<R, Box<dyn Any + Send>>
 where
     F: FnOnce() -> R + std::panic::UnwindSafe,
-    H: Fn(&PanicInfo) -> () + 'static + Sync + Send,
+    H: Fn(&PanicHookInfo) + 'static + Sync + Send,
 {
     let old = panic::take_hook();
     panic::set_hook(Box::new(handler));
@@ -285,8 +215,11 @@ fn main() -> Result<()> {
         run_game,
     );
     match rs {
-        Err(_) => println!("{}", panic_buf.lock().unwrap()),
-        Ok(r) => r?,
-    };
-    Ok(())
+        Err(_) => {
+            println!("{}", panic_buf.lock().unwrap());
+            // the exit code of an uncaught panic
+            std::process::exit(101)
+        }
+        Ok(r) => r,
+    }
 }
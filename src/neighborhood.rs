@@ -0,0 +1,125 @@
+use anyhow::{anyhow, Error, Result};
+use std::{fmt::Display, ops::RangeInclusive, str::FromStr};
+
+use crate::gol::Point;
+
+/// Shape of the cells around a center which count as its neighbors
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Neighborhood {
+    Moore,
+    VonNeumann,
+    /// Cells within euclidean distance `r + 1/2` of the center
+    Circular,
+    /// Hexagonal grid mapped onto the square one by skewing, cells in the
+    /// north east and south west corners of the Moore neighborhood are left out
+    Hexagonal,
+}
+
+impl Neighborhood {
+    /// Horizontal extent, relative to the center, of row `dy` of a range `r`
+    /// neighborhood or `None` if the row is outside of it
+    pub fn row(self, r: u32, dy: i64) -> Option<RangeInclusive<i64>> {
+        let r = r as i64;
+        if dy.abs() > r {
+            return None;
+        }
+        let half = match self {
+            Neighborhood::Moore => r,
+            Neighborhood::VonNeumann => r - dy.abs(),
+            Neighborhood::Circular => {
+                let lim = r * r + r - dy * dy;
+                (0..=r).take_while(|dx| dx * dx <= lim).last().unwrap_or(0)
+            }
+            Neighborhood::Hexagonal => return Some((dy - r).max(-r)..=(dy + r).min(r)),
+        };
+        Some(-half..=half)
+    }
+    /// Whether the cell at offset `d` from the center is part of a range `r`
+    /// neighborhood
+    pub fn contains(self, r: u32, d: &Point) -> bool {
+        self.row(r, d.y).is_some_and(|xs| xs.contains(&d.x))
+    }
+    /// Offsets of every cell in the neighborhood, including the center
+    pub fn offsets(self, r: u32) -> impl Iterator<Item = Point> {
+        let r = r as i64;
+        (-r..=r).flat_map(move |dy| {
+            self.row(r as u32, dy)
+                .into_iter()
+                .flatten()
+                .map(move |dx| Point { x: dx, y: dy })
+        })
+    }
+    /// Number of cells in the neighborhood, including the center
+    pub fn size(self, r: u32) -> u32 {
+        (-(r as i64)..=r as i64)
+            .filter_map(|dy| self.row(r, dy))
+            .map(|xs| xs.count() as u32)
+            .sum()
+    }
+}
+
+impl FromStr for Neighborhood {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_uppercase().as_str() {
+            "M" => Ok(Neighborhood::Moore),
+            "N" => Ok(Neighborhood::VonNeumann),
+            "C" => Ok(Neighborhood::Circular),
+            "H" => Ok(Neighborhood::Hexagonal),
+            _ => Err(anyhow!("unknown neighborhood '{}'", s)),
+        }
+    }
+}
+
+impl Display for Neighborhood {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Neighborhood::Moore => "M",
+                Neighborhood::VonNeumann => "N",
+                Neighborhood::Circular => "C",
+                Neighborhood::Hexagonal => "H",
+            }
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sizes() {
+        assert_eq!(Neighborhood::Moore.size(1), 9);
+        assert_eq!(Neighborhood::Moore.size(5), 121);
+        assert_eq!(Neighborhood::VonNeumann.size(1), 5);
+        assert_eq!(Neighborhood::VonNeumann.size(2), 13);
+        assert_eq!(Neighborhood::Circular.size(1), 9);
+        assert_eq!(Neighborhood::Circular.size(2), 21);
+        assert_eq!(Neighborhood::Hexagonal.size(1), 7);
+        assert_eq!(Neighborhood::Hexagonal.size(2), 19);
+    }
+    #[test]
+    fn test_hex_corners() {
+        let hex = Neighborhood::Hexagonal;
+        assert!(!hex.contains(1, &Point { x: 1, y: -1 }));
+        assert!(!hex.contains(1, &Point { x: -1, y: 1 }));
+        assert!(hex.contains(1, &Point { x: -1, y: -1 }));
+        assert!(hex.contains(1, &Point { x: 1, y: 1 }));
+    }
+    #[test]
+    fn test_offsets_match_rows() {
+        for n in [
+            Neighborhood::Moore,
+            Neighborhood::VonNeumann,
+            Neighborhood::Circular,
+            Neighborhood::Hexagonal,
+        ] {
+            assert_eq!(n.offsets(4).count() as u32, n.size(4));
+            assert!(n.offsets(4).any(|p| p == Point { x: 0, y: 0 }));
+        }
+    }
+}
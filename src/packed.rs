@@ -0,0 +1,176 @@
+use rayon::prelude::*;
+
+use crate::{
+    gol::{Board, Point},
+    neighborhood::Neighborhood,
+    rule::LifeRule,
+};
+
+/// Row shifted one cell along, bit `x` of the result is the cell at `x + 1`
+/// (`east`) or `x - 1` (west) wrapping around the ends of the row
+fn shifted(row: &[u64], width: u32, east: bool) -> Vec<u64> {
+    let last = row.len() - 1;
+    let (end_word, end_bit) = (((width - 1) / 64) as usize, (width - 1) % 64);
+    let mut out: Vec<u64> = (0..row.len())
+        .map(|i| {
+            if east {
+                (row[i] >> 1) | row.get(i + 1).map_or(0, |w| w << 63)
+            } else {
+                (row[i] << 1) | if i > 0 { row[i - 1] >> 63 } else { 0 }
+            }
+        })
+        .collect();
+    if east {
+        out[end_word] &= !(1 << end_bit);
+        out[end_word] |= (row[0] & 1) << end_bit;
+    } else {
+        out[0] = (out[0] & !1) | ((row[end_word] >> end_bit) & 1);
+        if !width.is_multiple_of(64) {
+            out[last] &= (1 << (width % 64)) - 1;
+        }
+    }
+    out
+}
+
+/// Add one bit to every cell of a bit-sliced counter, `planes[k]` holding
+/// bit `k` of each cell's count
+fn add(planes: &mut [u64; 4], mut carry: u64) {
+    for p in planes.iter_mut() {
+        let sum = *p ^ carry;
+        carry &= *p;
+        *p = sum;
+    }
+}
+
+/// Cells whose count in `planes` is one of the counts in the bitset `set`
+fn matching(planes: &[u64; 4], set: u16) -> u64 {
+    (0..=8)
+        .filter(|n| set & (1 << n) != 0)
+        .map(|n| {
+            planes.iter().enumerate().fold(!0, |acc, (k, p)| {
+                acc & if n & (1 << k) != 0 { *p } else { !*p }
+            })
+        })
+        .fold(0, |acc, m| acc | m)
+}
+
+/// Two-state outer-totalistic rule applied to a word of 64 cells at once.
+/// Neighbor counts are summed with a bit-sliced adder so every cell of a
+/// word is updated with a handful of bitwise operations
+#[derive(Clone, Debug)]
+pub struct WordRule {
+    birth: u16,
+    survive: u16,
+    /// Neighbors as (row, column) of the 3x3 block around a cell
+    used: Vec<(usize, usize)>,
+}
+
+impl WordRule {
+    pub fn new(birth: u16, survive: u16, hood: Neighborhood) -> Self {
+        let used = (0..3)
+            .flat_map(|dy| (0..3).map(move |dx| (dy, dx)))
+            .filter(|(dy, dx)| {
+                let d = Point {
+                    x: *dx as i64 - 1,
+                    y: *dy as i64 - 1,
+                };
+                (dx, dy) != (&1, &1) && hood.contains(1, &d)
+            })
+            .collect();
+        Self {
+            birth,
+            survive,
+            used,
+        }
+    }
+    /// `None` unless `rule` is two-state and outer-totalistic
+    pub fn from_rule(rule: &LifeRule) -> Option<Self> {
+        let (birth, survive) = rule.totalistic().filter(|_| rule.states() == 2)?;
+        Some(Self::new(birth, survive, rule.neighborhood()))
+    }
+    /// Next state of a word of cells, `block[dy][dx]` is the word holding
+    /// the cells at offset `(dx - 1, dy - 1)` from each of them
+    pub fn next(&self, block: &[[u64; 3]; 3]) -> u64 {
+        let mut planes = [0; 4];
+        for (dy, dx) in &self.used {
+            add(&mut planes, block[*dy][*dx]);
+        }
+        let alive = block[1][1];
+        (alive & matching(&planes, self.survive)) | (!alive & matching(&planes, self.birth))
+    }
+}
+
+/// Advance `board` by one generation, a word at a time. Rows wrap around
+/// as on a torus whatever the board's topology
+pub fn step(board: &Board, rule: &WordRule) -> Board {
+    let (w, h) = (board.width(), board.height());
+    let shifts: Vec<[Vec<u64>; 3]> = (0..h)
+        .into_par_iter()
+        .map(|y| {
+            let row = board.row(y);
+            [shifted(row, w, false), row.to_vec(), shifted(row, w, true)]
+        })
+        .collect();
+    let stride = shifts[0][1].len();
+    let mut out = vec![0; stride * h as usize];
+    out.par_chunks_mut(stride).enumerate().for_each(|(y, row)| {
+        let rows = [(y + h as usize - 1) % h as usize, y, (y + 1) % h as usize];
+        for (i, word) in row.iter_mut().enumerate() {
+            *word = rule.next(&rows.map(|r| [0, 1, 2].map(|dx| shifts[r][dx][i])));
+        }
+    });
+    Board::from_rows(w, out).with_topology(board.topology())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn soup(width: u32, height: u32) -> Board {
+        Board::new(
+            width,
+            (0..width * height)
+                .map(|i| (i.wrapping_mul(2654435761) >> 13) % 3 == 0)
+                .collect(),
+        )
+    }
+
+    fn naive(board: &Board, rule: &LifeRule) -> Vec<u8> {
+        (0..board.height() as i64)
+            .flat_map(|y| (0..board.width() as i64).map(move |x| Point { x, y }))
+            .map(|pt| {
+                let ns = board.neighbors(&pt, rule.neighborhood());
+                rule.next(board.state(pt), &ns)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_shifted_wraps() {
+        let row = [1 | (1 << 4)];
+        assert_eq!(shifted(&row, 5, true), [(1 << 3) | (1 << 4)]);
+        assert_eq!(shifted(&row, 5, false), [1 | (1 << 1)]);
+        let row = [1 << 63, 1];
+        assert_eq!(shifted(&row, 65, true), [(1 << 62) | (1 << 63), 0]);
+        assert_eq!(shifted(&row, 65, false), [1, 1]);
+    }
+    #[test]
+    fn test_matches_per_cell() {
+        for rs in [
+            "B3/S23",
+            "B36/S23",
+            "B2/S",
+            "B2/S34H",
+            "B1/S013V",
+            "B0123478/S34678",
+        ] {
+            let rule: LifeRule = rs.parse().unwrap();
+            let word_rule = WordRule::from_rule(&rule).unwrap();
+            for (w, h) in [(1, 3), (5, 7), (64, 4), (70, 9), (130, 5)] {
+                let board = soup(w, h);
+                let next = step(&board, &word_rule);
+                assert_eq!(next.states(), naive(&board, &rule), "{} on {}x{}", rs, w, h);
+            }
+        }
+    }
+}
@@ -0,0 +1,236 @@
+use anyhow::{anyhow, Context, Result};
+use std::{
+    fs,
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use crate::{
+    cells,
+    gol::Board,
+    life,
+    macrocell::{self, Macrocell},
+    pbm, pgm,
+    pgm::Threshold,
+    rle,
+    rule::AnyRule,
+};
+
+/// What a pattern file says about the pattern besides its cells
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Metadata {
+    /// Rule as written in the file, it isn't checked as it may be one this
+    /// crate doesn't run
+    pub rule: Option<String>,
+    pub name: Option<String>,
+    pub author: Option<String>,
+    /// Comment lines in order
+    pub comments: Vec<String>,
+}
+
+/// A pattern read from a file along with its metadata, which is empty for
+/// formats with no room for any
+#[derive(Clone, Debug)]
+pub struct Pattern {
+    pub board: Board,
+    pub meta: Metadata,
+}
+
+impl From<Board> for Pattern {
+    fn from(board: Board) -> Self {
+        Self {
+            board,
+            meta: Metadata::default(),
+        }
+    }
+}
+
+/// File formats patterns can be read from and written to
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Format {
+    Pgm,
+    Pbm,
+    /// PBM as `P1` text rather than packed `P4`, never named by an extension
+    PlainPbm,
+    Rle,
+    /// Plaintext `.cells`
+    Cells,
+    /// Life 1.05 or 1.06, written as 1.06
+    Life,
+    /// Golly's quadtree format
+    Macrocell,
+}
+
+impl Format {
+    /// Format named by the extension of `path`
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        Some(match extension.as_str() {
+            "pgm" => Format::Pgm,
+            "pbm" => Format::Pbm,
+            "rle" => Format::Rle,
+            "cells" => Format::Cells,
+            "lif" | "life" => Format::Life,
+            "mc" => Format::Macrocell,
+            _ => return None,
+        })
+    }
+    /// Format `bytes` are in judging by how they start, `None` when they
+    /// could be in more than one
+    pub fn sniff(bytes: &[u8]) -> Option<Self> {
+        let first = bytes
+            .split(|b| *b == b'\n')
+            .map(<[u8]>::trim_ascii)
+            .find(|l| !l.is_empty())?;
+        Some(match first {
+            [b'P', b'1', ..] => Format::PlainPbm,
+            [b'P', b'4', ..] => Format::Pbm,
+            [b'P', b'2' | b'5', ..] => Format::Pgm,
+            _ if first.starts_with(b"#Life 1.0") => Format::Life,
+            _ if first.starts_with(b"[M2]") => Format::Macrocell,
+            [b'!', ..] => Format::Cells,
+            [b'x', ..] | [b'#', b'N' | b'C' | b'c' | b'O', ..] => Format::Rle,
+            _ => return None,
+        })
+    }
+}
+
+/// Read the pattern in the file at `path`, in the format its contents or
+/// failing that its extension name. `threshold` picks the live cells of
+/// greyscale images
+pub fn load(path: &Path, threshold: Threshold) -> Result<Pattern> {
+    let bytes = fs::read(path).with_context(|| format!("failed to open {}", path.display()))?;
+    let mut f = bytes.as_slice();
+    let format = Format::sniff(&bytes)
+        .or_else(|| Format::from_path(path))
+        .ok_or_else(|| anyhow!("can't tell the format of {}", path.display()))?;
+    match format {
+        Format::Pgm => pgm::read(&mut f, threshold).map(Pattern::from),
+        Format::Pbm | Format::PlainPbm => pbm::read(&mut f).map(Pattern::from),
+        Format::Rle => rle::read(&mut f),
+        Format::Cells => cells::read(&mut f),
+        Format::Life => life::read(&mut f),
+        Format::Macrocell => macrocell::read(&mut f).and_then(|mc| {
+            Ok(Pattern {
+                board: mc.to_board()?,
+                meta: mc.meta,
+            })
+        }),
+    }
+    .with_context(|| format!("failed to read {}", path.display()))
+}
+
+/// Quadtree of the pattern at `path` if it's a macrocell file, so it can
+/// be run without fitting on a board
+pub fn load_macrocell(path: &Path) -> Result<Option<Macrocell>> {
+    let bytes = fs::read(path).with_context(|| format!("failed to open {}", path.display()))?;
+    if Format::sniff(&bytes).or_else(|| Format::from_path(path)) != Some(Format::Macrocell) {
+        return Ok(None);
+    }
+    macrocell::read(&mut bytes.as_slice())
+        .map(Some)
+        .with_context(|| format!("failed to read {}", path.display()))
+}
+
+/// Write `board` to `path` in the format its extension names, `rule` is
+/// recorded by the formats which have room for it
+pub fn save(path: &Path, board: &Board, rule: &AnyRule) -> Result<()> {
+    let format = Format::from_path(path).ok_or_else(|| {
+        anyhow!(
+            "unsupported output format '{}'",
+            path.extension().unwrap_or_default().to_string_lossy()
+        )
+    })?;
+    save_as(path, format, board, rule)
+}
+
+/// Write `board` to `path` in `format` whatever the extension says
+pub fn save_as(path: &Path, format: Format, board: &Board, rule: &AnyRule) -> Result<()> {
+    if format == Format::Macrocell {
+        return save_macrocell(path, &Macrocell::from_board(board), rule);
+    }
+    let f = File::create(path).with_context(|| format!("failed to create {}", path.display()))?;
+    let mut f = BufWriter::new(f);
+    match format {
+        Format::Pgm => pgm::write(&mut f, board),
+        Format::Pbm => pbm::write(&mut f, board),
+        Format::PlainPbm => pbm::write_plain(&mut f, board),
+        Format::Rle => rle::write(&mut f, board, rule),
+        Format::Cells => cells::write(&mut f, board),
+        Format::Life => life::write(&mut f, board),
+        Format::Macrocell => unreachable!("written above"),
+    }
+    .and_then(|_| Ok(f.flush()?))
+    .with_context(|| format!("failed to write {}", path.display()))
+}
+
+/// Write `mc` to `path` as a macrocell file for `rule`
+pub fn save_macrocell(path: &Path, mc: &Macrocell, rule: &AnyRule) -> Result<()> {
+    let f = File::create(path).with_context(|| format!("failed to create {}", path.display()))?;
+    let mut f = BufWriter::new(f);
+    macrocell::write(&mut f, mc, rule)
+        .and_then(|_| Ok(f.flush()?))
+        .with_context(|| format!("failed to write {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gol::Point;
+
+    #[test]
+    fn test_save_and_load() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("gol-rs-pattern-{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        let mut board = Board::empty(7, 4);
+        board.set(Point { x: 3, y: 1 }, true);
+        let rule = AnyRule::default();
+        for name in ["board.PGM", "board.pbm", "board.rle", "board.cells"] {
+            let path = dir.join(name);
+            save(&path, &board, &rule)?;
+            let loaded = load(&path, Threshold::default())?.board;
+            assert_eq!(loaded.states(), board.states());
+        }
+        // Life 1.06 and macrocell only keep the live cells
+        for name in ["board.lif", "board.mc"] {
+            save(&dir.join(name), &board, &rule)?;
+            let loaded = load(&dir.join(name), Threshold::default())?.board;
+            assert_eq!((loaded.width(), loaded.alive()), (1, 1));
+        }
+        assert!(load_macrocell(&dir.join("board.mc"))?.is_some());
+        assert!(load_macrocell(&dir.join("board.lif"))?.is_none());
+        assert!(save(&dir.join("board.xyz"), &board, &rule).is_err());
+        save_as(&dir.join("plain.pbm"), Format::PlainPbm, &board, &rule)?;
+        assert!(std::fs::read(dir.join("plain.pbm"))?.starts_with(b"P1\n7 4\n"));
+        let loaded = load(&dir.join("plain.pbm"), Threshold::default())?.board;
+        assert_eq!(loaded.states(), board.states());
+        // formats are found by their contents whatever the file is called
+        std::fs::rename(dir.join("board.pbm"), dir.join("board.rle"))?;
+        let loaded = load(&dir.join("board.rle"), Threshold::default())?.board;
+        assert_eq!(loaded.alive(), 1);
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+    #[test]
+    fn test_sniff() {
+        let cases: [(&[u8], _); 9] = [
+            (b"[M2] (golly 2.0)\n", Some(Format::Macrocell)),
+            (b"P4 1 1\n\x80", Some(Format::Pbm)),
+            (b"P1 1 1\n1\n", Some(Format::PlainPbm)),
+            (b"P2 1 1 1 1", Some(Format::Pgm)),
+            (b"\n#Life 1.06\n0 0\n", Some(Format::Life)),
+            (b"!Name: Glider\n.O\n", Some(Format::Cells)),
+            (b"#N Glider\nx = 1, y = 1\no!", Some(Format::Rle)),
+            (b"x = 1, y = 1\no!", Some(Format::Rle)),
+            (b".O\nOO\n", None),
+        ];
+        for (bytes, format) in cases {
+            assert_eq!(Format::sniff(bytes), format);
+        }
+    }
+}
@@ -0,0 +1,137 @@
+use anyhow::{bail, ensure, Result};
+use std::io::{Read, Write};
+
+use crate::{gol::Board, macrocell::MAX_BOARD, pgm::Fields};
+
+/// Read a PBM, either packed (P4) or ASCII (P1), set (black) pixels are
+/// alive
+pub fn read(f: &mut dyn Read) -> Result<Board> {
+    let mut bytes = Vec::new();
+    f.read_to_end(&mut bytes)?;
+    let mut fields = Fields::new(&bytes);
+    let packed = match fields.next_field().unwrap_or_default() {
+        b"P4" => true,
+        b"P1" => false,
+        _ => bail!("not a P1 or P4 pbm"),
+    };
+    let width: u32 = fields.number("width")?;
+    let height: u32 = fields.number("height")?;
+    ensure!(width > 0 && height > 0, "empty {}x{} image", width, height);
+    ensure!(
+        width as u64 * height as u64 <= MAX_BOARD,
+        "the image is {}x{}, too large for a board",
+        width,
+        height
+    );
+    let count = width as usize * height as usize;
+    let cells = if packed {
+        // rows are padded out to whole bytes, high bit first
+        let stride = (width as usize).div_ceil(8);
+        let raster = fields.raster()?;
+        ensure!(
+            raster.len() == stride * height as usize,
+            "expected {} bytes for {}x{}, found {}",
+            stride * height as usize,
+            width,
+            height,
+            raster.len()
+        );
+        raster
+            .chunks(stride)
+            .flat_map(|row| (0..width as usize).map(|x| row[x / 8] & 0x80 >> (x % 8) != 0))
+            .collect()
+    } else {
+        // digits needn't be separated, so they're read one byte at a time
+        let mut cells = Vec::with_capacity(count);
+        while let Some(b) = fields.next_byte() {
+            match b {
+                b'0' | b'1' => cells.push(b == b'1'),
+                b => bail!("invalid pixel '{}'", b as char),
+            }
+        }
+        ensure!(
+            cells.len() == count,
+            "expected {} pixels for {}x{}, found {}",
+            count,
+            width,
+            height,
+            cells.len()
+        );
+        cells
+    };
+    Ok(Board::new(width, cells))
+}
+
+/// Write `board` as a packed (P4) PBM, eight cells to a byte
+pub fn write(f: &mut dyn Write, board: &Board) -> Result<()> {
+    write!(f, "P4\n{} {}\n", board.width(), board.height())?;
+    let width = board.width() as usize;
+    let mut row = vec![0; width.div_ceil(8)];
+    for cells in board.states().chunks(width) {
+        row.fill(0);
+        for (x, _) in cells.iter().enumerate().filter(|(_, s)| **s == 1) {
+            row[x / 8] |= 0x80 >> (x % 8);
+        }
+        f.write_all(&row)?;
+    }
+    Ok(())
+}
+
+/// Write `board` as an ASCII (P1) PBM
+pub fn write_plain(f: &mut dyn Write, board: &Board) -> Result<()> {
+    write!(f, "P1\n{} {}\n", board.width(), board.height())?;
+    for cells in board.states().chunks(board.width() as usize) {
+        // lines are kept within the 70 characters the format allows
+        for line in cells.chunks(70) {
+            let line: String = line
+                .iter()
+                .map(|s| if *s == 1 { '1' } else { '0' })
+                .collect();
+            writeln!(f, "{}", line)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gol::Point;
+
+    fn board() -> Board {
+        let mut board = Board::empty(10, 3);
+        for (x, y) in [(0, 0), (9, 0), (8, 1), (3, 2)] {
+            board.set(Point { x, y }, true);
+        }
+        board
+    }
+
+    #[test]
+    fn test_read() -> Result<()> {
+        let plain = b"P1\n# glider\n3 3\n010\n0 0 1\n111\n";
+        let board = read(&mut plain.as_slice())?;
+        assert_eq!(board.states(), [0, 1, 0, 0, 0, 1, 1, 1, 1]);
+        let packed = [b"P4 9 1\n".as_slice(), &[0b1000_0001, 0b1000_0000]].concat();
+        let board = read(&mut packed.as_slice())?;
+        assert_eq!(board.states(), [1, 0, 0, 0, 0, 0, 0, 1, 1]);
+        assert!(read(&mut b"P1 2 2 0 1 1".as_slice()).is_err());
+        assert!(read(&mut b"P1 1 1 2".as_slice()).is_err());
+        assert!(read(&mut [b"P4 9 1\n".as_slice(), &[0]].concat().as_slice()).is_err());
+        // the header alone is too large for a board
+        assert!(read(&mut b"P1 100000 100000\n1".as_slice()).is_err());
+        Ok(())
+    }
+    #[test]
+    fn test_write_round_trip() -> Result<()> {
+        let board = board();
+        let mut out = Vec::new();
+        write(&mut out, &board)?;
+        assert_eq!(out.len(), b"P4\n10 3\n".len() + 2 * 3);
+        assert_eq!(read(&mut out.as_slice())?.states(), board.states());
+        let mut out = Vec::new();
+        write_plain(&mut out, &board)?;
+        assert!(out.starts_with(b"P1\n10 3\n1000000001\n"));
+        assert_eq!(read(&mut out.as_slice())?.states(), board.states());
+        Ok(())
+    }
+}
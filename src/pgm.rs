@@ -0,0 +1,264 @@
+use anyhow::{anyhow, bail, ensure, Context, Result};
+use std::{
+    io::{Read, Write},
+    str::FromStr,
+};
+
+use crate::gol::Board;
+
+/// Which grey levels are read as live cells
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Threshold {
+    /// Fraction of the maximum grey level a pixel has to reach to be
+    /// alive, `1.0` only takes full white
+    pub level: f64,
+    /// Dark pixels are alive instead, for images drawn black on white
+    pub invert: bool,
+}
+
+impl Default for Threshold {
+    fn default() -> Self {
+        Self {
+            level: 1.0,
+            invert: false,
+        }
+    }
+}
+
+impl Threshold {
+    fn alive(&self, grey: u16, maxval: u16) -> bool {
+        let grey = if self.invert { maxval - grey } else { grey };
+        grey as f64 >= self.level * maxval as f64
+    }
+}
+
+/// Whitespace separated fields of a PNM header, with `#` comments running
+/// to the end of the line skipped
+pub(crate) struct Fields<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Fields<'a> {
+    pub(crate) fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, pos: 0 }
+    }
+    fn skip_blank(&mut self) {
+        while let Some(&b) = self.bytes.get(self.pos) {
+            match b {
+                b'#' => {
+                    while self
+                        .bytes
+                        .get(self.pos)
+                        .is_some_and(|b| !matches!(b, b'\n' | b'\r'))
+                    {
+                        self.pos += 1;
+                    }
+                }
+                b if b.is_ascii_whitespace() => self.pos += 1,
+                _ => break,
+            }
+        }
+    }
+    pub(crate) fn next_field(&mut self) -> Option<&'a [u8]> {
+        self.skip_blank();
+        let start = self.pos;
+        while self
+            .bytes
+            .get(self.pos)
+            .is_some_and(|b| !b.is_ascii_whitespace() && *b != b'#')
+        {
+            self.pos += 1;
+        }
+        (self.pos > start).then(|| &self.bytes[start..self.pos])
+    }
+    /// Next byte which isn't whitespace or in a comment
+    pub(crate) fn next_byte(&mut self) -> Option<u8> {
+        self.skip_blank();
+        let b = self.bytes.get(self.pos).copied();
+        self.pos += 1;
+        b
+    }
+    /// Next field as a number, `what` names it in errors
+    pub(crate) fn number<T: FromStr>(&mut self, what: &str) -> Result<T> {
+        let field = self
+            .next_field()
+            .ok_or_else(|| anyhow!("missing {}", what))?;
+        std::str::from_utf8(field)
+            .ok()
+            .and_then(|f| f.parse().ok())
+            .ok_or_else(|| anyhow!("invalid {} '{}'", what, String::from_utf8_lossy(field)))
+    }
+    /// Binary data after the header, which ends with a single whitespace
+    /// byte
+    pub(crate) fn raster(self) -> Result<&'a [u8]> {
+        match self.bytes.get(self.pos) {
+            Some(b) if b.is_ascii_whitespace() => Ok(&self.bytes[self.pos + 1..]),
+            _ => bail!("missing whitespace after the header"),
+        }
+    }
+}
+
+/// Read a PGM, either binary (P5) with 8 or 16 bits per pixel or ASCII
+/// (P2), with `threshold` picking which pixels are alive
+pub fn read(f: &mut dyn Read, threshold: Threshold) -> Result<Board> {
+    ensure!(
+        (0.0..=1.0).contains(&threshold.level),
+        "threshold must be between 0 and 1, got {}",
+        threshold.level
+    );
+    let mut bytes = Vec::new();
+    f.read_to_end(&mut bytes)?;
+    let mut fields = Fields::new(&bytes);
+    let magic = fields.next_field().unwrap_or_default();
+    let binary = match magic {
+        b"P5" => true,
+        b"P2" => false,
+        _ => bail!("not a P2 or P5 pgm"),
+    };
+    let width: u32 = fields.number("width")?;
+    let height: u32 = fields.number("height")?;
+    let maxval: u16 = fields.number("max grey")?;
+    ensure!(width > 0 && height > 0, "empty {}x{} image", width, height);
+    ensure!(maxval > 0, "max grey must be at least 1");
+    let count = width as usize * height as usize;
+    let pixels: Vec<u16> = if binary {
+        let depth = if maxval < 256 { 1 } else { 2 };
+        let raster = fields.raster()?;
+        ensure!(
+            raster.len() == count * depth,
+            "expected {} pixels for {}x{}, found {}",
+            count,
+            width,
+            height,
+            raster.len() / depth
+        );
+        raster
+            .chunks(depth)
+            .map(|p| p.iter().fold(0, |v, b| v << 8 | *b as u16))
+            .collect()
+    } else {
+        let pixels = (0..count)
+            .map(|i| {
+                fields.number("pixel").with_context(|| {
+                    format!(
+                        "expected {} pixels for {}x{}, found {}",
+                        count, width, height, i
+                    )
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        ensure!(
+            fields.next_field().is_none(),
+            "more than {} pixels for {}x{}",
+            count,
+            width,
+            height
+        );
+        pixels
+    };
+    ensure!(
+        pixels.iter().all(|p| *p <= maxval),
+        "pixel above the max grey of {}",
+        maxval
+    );
+    Ok(Board::new(
+        width,
+        pixels
+            .into_iter()
+            .map(|p| threshold.alive(p, maxval))
+            .collect(),
+    ))
+}
+
+/// Write `board` as a binary (P5) PGM with live cells white and the rest
+/// black
+pub fn write(f: &mut dyn Write, board: &Board) -> Result<()> {
+    write!(f, "P5\n{} {}\n255\n", board.width(), board.height())?;
+    let pixels: Vec<u8> = board
+        .states()
+        .into_iter()
+        .map(|s| if s == 1 { 255 } else { 0 })
+        .collect();
+    f.write_all(&pixels)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gol::Point;
+
+    #[test]
+    fn test_read() -> Result<()> {
+        let mut bytes = b"P5 3 2 255 ".to_vec();
+        bytes.extend([0, 255, 0, 255, 128, 0]);
+        let board = read(&mut bytes.as_slice(), Threshold::default())?;
+        assert_eq!((board.width(), board.height()), (3, 2));
+        assert_eq!(board.alive(), 2);
+        assert!(board[Point { x: 1, y: 0 }] && board[Point { x: 0, y: 1 }]);
+        // pixel data which happens to be whitespace isn't a separator
+        let mut bytes = b"P5 2 2 255\n".to_vec();
+        bytes.extend([b' ', 255, b'\n', 255]);
+        assert_eq!(
+            read(&mut bytes.as_slice(), Threshold::default())?.alive(),
+            2
+        );
+        Ok(())
+    }
+    #[test]
+    fn test_read_variants() -> Result<()> {
+        let ascii = b"P2\n# made by hand\n3 # width\n2\n# max\n15\n0 15 8\n15 0 7\n";
+        let board = read(&mut ascii.as_slice(), Threshold::default())?;
+        assert_eq!(board.states(), [0, 1, 0, 1, 0, 0]);
+        let half = Threshold {
+            level: 0.5,
+            invert: false,
+        };
+        let board = read(&mut ascii.as_slice(), half)?;
+        assert_eq!(board.states(), [0, 1, 1, 1, 0, 0]);
+        let inverted = Threshold {
+            level: 1.0,
+            invert: true,
+        };
+        let board = read(&mut ascii.as_slice(), inverted)?;
+        assert_eq!(board.states(), [1, 0, 0, 0, 1, 0]);
+
+        let mut wide = b"P5 2 1 65535\n".to_vec();
+        wide.extend([0xff, 0xff, 0xff, 0x00]);
+        let board = read(&mut wide.as_slice(), Threshold::default())?;
+        assert_eq!(board.states(), [1, 0]);
+        Ok(())
+    }
+    #[test]
+    fn test_read_errors() {
+        let read = |b: &[u8]| read(&mut &b[..], Threshold::default());
+        assert!(read(b"P6 3 2 255 ").is_err());
+        assert!(read(b"P2 3 2").is_err());
+        // height doesn't match the pixels
+        assert!(read(b"P2 2 2 1 0 1 1").is_err());
+        assert!(read(b"P2 2 1 1 0 1 1").is_err());
+        assert!(read(b"P5 2 2 255\n\x00\x01\x02").is_err());
+        assert!(read(b"P2 2 1 1 0 2").is_err());
+        assert!(read(b"P2 0 1 1").is_err());
+        let over = Threshold {
+            level: 1.5,
+            invert: false,
+        };
+        assert!(super::read(&mut &b"P2 1 1 1 1"[..], over).is_err());
+    }
+    #[test]
+    fn test_write_round_trip() -> Result<()> {
+        let mut board = Board::empty(5, 3);
+        board.set(Point { x: 4, y: 0 }, true);
+        board.set(Point { x: 0, y: 2 }, true);
+        let mut out = Vec::new();
+        write(&mut out, &board)?;
+        assert!(out.starts_with(b"P5\n5 3\n255\n"));
+        assert_eq!(
+            read(&mut out.as_slice(), Threshold::default())?.states(),
+            board.states()
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,288 @@
+use anyhow::{anyhow, bail, ensure, Context, Result};
+use std::io::{Read, Write};
+
+use crate::{
+    gol::Board,
+    macrocell::MAX_BOARD,
+    pattern::{Metadata, Pattern},
+    rule::AnyRule,
+};
+
+/// Lines of RLE output are kept within this many characters
+const LINE: usize = 70;
+
+/// Value of the `x = ..` style `key` in one comma separated part of the
+/// header
+fn header_value<'a>(part: &'a str, key: &str) -> Option<&'a str> {
+    let (k, v) = part.split_once('=')?;
+    (k.trim() == key).then(|| v.trim())
+}
+
+/// State a run of `tag` stands for, `prefix` being the preceding `p`-`y`
+/// letter of a multi-state one
+fn state(prefix: Option<char>, tag: char) -> Result<u8> {
+    Ok(match (prefix, tag) {
+        (None, '.' | 'b') => 0,
+        (None, 'A'..='X') => tag as u8 - b'A' + 1,
+        // two state patterns use any other lower case letter for live cells
+        (None, 'a'..='z') => 1,
+        (Some(p), 'A'..='X') => {
+            let state = (p as u32 - 'o' as u32) * 24 + (tag as u32 - 'A' as u32 + 1);
+            u8::try_from(state).map_err(|_| anyhow!("state {}{} is too high", p, tag))?
+        }
+        (Some(p), _) => bail!("invalid state '{}{}'", p, tag),
+        (None, _) => bail!("invalid character '{}'", tag),
+    })
+}
+
+/// Read an RLE pattern, its board is as large as the header's `x` and `y`
+/// or the cells given if they go further down. Rows may not run past `x`
+pub fn read(f: &mut dyn Read) -> Result<Pattern> {
+    let mut text = String::new();
+    f.read_to_string(&mut text)?;
+    let mut meta = Metadata::default();
+    let mut size = None;
+    let mut lines = text.lines().map(str::trim).peekable();
+    while let Some(line) = lines.next_if(|l| l.starts_with('#') || l.is_empty()) {
+        let tag = line.get(..2).unwrap_or(line);
+        let rest = line.get(2..).unwrap_or_default().trim().to_string();
+        match tag {
+            "#N" => meta.name = Some(rest),
+            "#O" => meta.author = Some(rest),
+            "#C" | "#c" => meta.comments.push(rest),
+            _ => (),
+        }
+    }
+    if let Some(header) = lines.next_if(|l| l.starts_with('x')) {
+        let mut parts = header.split(',');
+        let mut dimension = |key| -> Result<u32> {
+            let part = parts.next().unwrap_or_default();
+            let value =
+                header_value(part, key).ok_or_else(|| anyhow!("missing {} in header", key))?;
+            value
+                .parse()
+                .with_context(|| format!("invalid {} '{}' in header", key, value))
+        };
+        size = Some((dimension("x")?, dimension("y")?));
+        // rules such as Larger than Life ones have commas of their own
+        let rest: Vec<_> = parts.collect();
+        if let Some(rule) = header_value(&rest.join(","), "rule") {
+            meta.rule = Some(rule.to_string());
+        }
+    }
+
+    let (x, y) = size.unwrap_or((0, 0));
+    ensure!(
+        (x as u64).saturating_mul(y as u64) <= MAX_BOARD,
+        "the pattern is {}x{}, too large for a board",
+        x,
+        y
+    );
+    // without a header only the size of the board limits a row
+    let max_width = size.map_or(MAX_BOARD, |(x, _)| x as u64);
+    // rows with cells in them along with how far down they are, so runs of
+    // empty rows take no room
+    let mut rows: Vec<(u64, Vec<u8>)> = Vec::new();
+    let mut row_y: u64 = 0;
+    let mut cells: u64 = 0;
+    let mut count: Option<u32> = None;
+    let mut prefix = None;
+    'body: for line in lines.filter(|l| !l.starts_with('#')) {
+        for c in line.chars() {
+            match c {
+                '!' => break 'body,
+                '0'..='9' => {
+                    ensure!(prefix.is_none(), "run count inside state '{}'", c);
+                    let digit = c as u32 - '0' as u32;
+                    count = Some(
+                        count
+                            .unwrap_or(0)
+                            .checked_mul(10)
+                            .and_then(|n| n.checked_add(digit))
+                            .ok_or_else(|| anyhow!("run too long"))?,
+                    );
+                }
+                '$' => {
+                    ensure!(prefix.is_none(), "unfinished state before '$'");
+                    row_y += count.take().unwrap_or(1) as u64;
+                }
+                'p'..='y' if prefix.is_none() && c != 'o' => prefix = Some(c),
+                c if c.is_whitespace() => (),
+                tag => {
+                    let state = state(prefix.take(), tag)?;
+                    let run = count.take().unwrap_or(1) as u64;
+                    if rows.last().is_none_or(|(y, _)| *y != row_y) {
+                        rows.push((row_y, Vec::new()));
+                    }
+                    let (_, row) = rows.last_mut().expect("a row was just pushed");
+                    ensure!(
+                        row.len() as u64 + run <= max_width,
+                        "row {} runs past a width of {}",
+                        row_y,
+                        max_width
+                    );
+                    cells += run;
+                    ensure!(cells <= MAX_BOARD, "the pattern is too large for a board");
+                    row.resize(row.len() + run as usize, state);
+                }
+            }
+        }
+    }
+    ensure!(
+        prefix.is_none(),
+        "unfinished state at the end of the pattern"
+    );
+
+    // a `$` just before the `!` doesn't make the pattern any taller
+    let height = rows.last().map_or(0, |(y, _)| y + 1).max(y as u64);
+    let width = rows
+        .iter()
+        .map(|(_, r)| r.len() as u64)
+        .max()
+        .unwrap_or(0)
+        .max(x as u64);
+    ensure!(width > 0 && height > 0, "empty pattern");
+    ensure!(
+        width.saturating_mul(height) <= MAX_BOARD,
+        "the pattern is {}x{}, too large for a board",
+        width,
+        height
+    );
+    let width = width as usize;
+    let mut states = vec![0; width * height as usize];
+    for (y, row) in &rows {
+        states[*y as usize * width..][..row.len()].copy_from_slice(row);
+    }
+    Ok(Pattern {
+        board: Board::from_states(width as u32, states),
+        meta,
+    })
+}
+
+/// Tag for `state`, letters for multi-state rules and `b`/`o` otherwise
+fn tag(state: u8, multi: bool) -> String {
+    match (state, multi) {
+        (0, false) => "b".into(),
+        (_, false) => "o".into(),
+        (0, true) => ".".into(),
+        (s, true) if s <= 24 => char::from(b'A' + s - 1).into(),
+        (s, true) => {
+            let prefix = char::from(b'o' + (s - 1) / 24);
+            let letter = char::from(b'A' + (s - 1) % 24);
+            format!("{}{}", prefix, letter)
+        }
+    }
+}
+
+/// Write `board` as RLE for `rule`, trailing dead cells of each row are
+/// left out
+pub fn write(f: &mut dyn Write, board: &Board, rule: &AnyRule) -> Result<()> {
+    writeln!(
+        f,
+        "x = {}, y = {}, rule = {}",
+        board.width(),
+        board.height(),
+        rule
+    )?;
+    let multi = rule.states() > 2 || board.dying() > 0;
+    let mut runs: Vec<(u32, String)> = Vec::new();
+    let mut push = |run: u32, tag: String| match runs.last_mut() {
+        Some((n, t)) if *t == tag => *n += run,
+        _ => runs.push((run, tag)),
+    };
+    let states = board.states();
+    let rows: Vec<_> = states.chunks(board.width() as usize).collect();
+    let last = rows.iter().rposition(|r| r.iter().any(|s| *s != 0));
+    for (y, row) in rows.iter().enumerate().take(last.map_or(0, |l| l + 1)) {
+        if y > 0 {
+            push(1, "$".into());
+        }
+        let end = row.iter().rposition(|s| *s != 0).map_or(0, |e| e + 1);
+        for s in &row[..end] {
+            push(1, tag(*s, multi));
+        }
+    }
+    push(1, "!".into());
+    let mut line = String::new();
+    for (run, tag) in runs {
+        let item = match run {
+            1 => tag,
+            n => format!("{}{}", n, tag),
+        };
+        if line.len() + item.len() > LINE {
+            writeln!(f, "{}", line)?;
+            line.clear();
+        }
+        line.push_str(&item);
+    }
+    writeln!(f, "{}", line)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read() -> Result<()> {
+        let text = "#N Glider\n#O Richard K. Guy\n#C The smallest spaceship\n\
+                    x = 3, y = 3, rule = B3/S23\nbob$2bo$3o!\n";
+        let rle = read(&mut text.as_bytes())?;
+        assert_eq!(rle.meta.name.as_deref(), Some("Glider"));
+        assert_eq!(rle.meta.author.as_deref(), Some("Richard K. Guy"));
+        assert_eq!(rle.meta.comments, ["The smallest spaceship"]);
+        assert_eq!(rle.meta.rule.as_deref(), Some("B3/S23"));
+        assert_eq!(rle.board.states(), [0, 1, 0, 0, 0, 1, 1, 1, 1]);
+
+        // wrapped lines, a run of empty rows and a header larger than the
+        // cells
+        let text = "x = 4, y = 5, rule = R2,C0,M1,S2..3,B3..3,NM\n2o\n$\n3$o\n!";
+        let rle = read(&mut text.as_bytes())?;
+        assert_eq!(rle.meta.rule.as_deref(), Some("R2,C0,M1,S2..3,B3..3,NM"));
+        assert_eq!((rle.board.width(), rle.board.height()), (4, 5));
+        assert_eq!(rle.board.alive(), 3);
+        assert_eq!(rle.board.states()[16], 1);
+        Ok(())
+    }
+    #[test]
+    fn test_read_multi_state() -> Result<()> {
+        let text = "x = 5, y = 1, rule = Wireworld\n.AB2C!";
+        assert_eq!(read(&mut text.as_bytes())?.board.states(), [0, 1, 2, 3, 3]);
+        let text = "#C été\nx = 2, y = 1\npAyO$!";
+        assert_eq!(read(&mut text.as_bytes())?.board.states(), [25, 255]);
+        assert!(read(&mut "yP!".as_bytes()).is_err());
+        assert!(read(&mut "p!".as_bytes()).is_err());
+        assert!(read(&mut "x = 1\no!".as_bytes()).is_err());
+        assert!(read(&mut "3o*!".as_bytes()).is_err());
+        Ok(())
+    }
+    #[test]
+    fn test_too_large() {
+        assert!(read(&mut "x = 200000, y = 200000\no!".as_bytes()).is_err());
+        assert!(read(&mut "x = 3, y = 1\n4000000000o!".as_bytes()).is_err());
+        assert!(read(&mut "x = 3, y = 1\n2o$2o$4o!".as_bytes()).is_err());
+        assert!(read(&mut "o4000000000$o!".as_bytes()).is_err());
+    }
+    #[test]
+    fn test_write_round_trip() -> Result<()> {
+        let mut states = vec![0; 100 * 4];
+        states[..90].fill(1);
+        states[100] = 1;
+        let board = Board::from_states(100, states);
+        let mut out = Vec::new();
+        write(&mut out, &board, &AnyRule::default())?;
+        let text = String::from_utf8(out)?;
+        assert_eq!(text, "x = 100, y = 4, rule = B3/S23\n90o$o!\n");
+        let rle = read(&mut text.as_bytes())?;
+        assert_eq!(rle.board.states(), board.states());
+
+        let board = Board::from_states(30, (0..30).map(|i| i % 3).collect());
+        let mut out = Vec::new();
+        write(&mut out, &board, &"B3/S23/3".parse()?)?;
+        let text = String::from_utf8(out)?;
+        assert!(text.lines().all(|l| l.len() <= LINE));
+        assert!(text.lines().nth(1).unwrap().starts_with(".AB.AB"));
+        assert_eq!(read(&mut text.as_bytes())?.board.states(), board.states());
+        Ok(())
+    }
+}
@@ -0,0 +1,696 @@
+use anyhow::{anyhow, ensure, Error, Result};
+use rayon::prelude::*;
+use std::{fmt::Display, str::FromStr};
+
+use crate::{
+    bgrid::Palette,
+    gol::{Board, CellState, Point},
+    ltl::LtlRule,
+    neighborhood::Neighborhood,
+    wireworld::Wireworld,
+};
+
+/// Cellular automaton on the range 1 neighborhood, which works out the
+/// next state of each cell from its own state and those around it
+pub trait Rule: Sync {
+    type State: CellState;
+
+    fn neighborhood(&self) -> Neighborhood;
+    /// Next state of `cell` with `neighbors` in the order of
+    /// [`Board::neighbor_cells`]
+    fn next(&self, cell: Self::State, neighbors: &[Self::State; 8]) -> Self::State;
+    /// How each state is drawn
+    fn palette(&self) -> Palette;
+}
+
+/// Next generation of `board` under `rule`, worked out cell by cell with
+/// the rows split across threads
+pub fn step<R: Rule>(board: &Board<R::State>, rule: &R) -> Board<R::State> {
+    let width = board.width() as usize;
+    let hood = rule.neighborhood();
+    let mut cells = vec![R::State::default(); width * board.height() as usize];
+    cells
+        .par_chunks_mut(width.max(1))
+        .enumerate()
+        .for_each(|(y, row)| {
+            for (x, c) in row.iter_mut().enumerate() {
+                let pt = Point {
+                    x: x as i64,
+                    y: y as i64,
+                };
+                *c = rule.next(board.cell(pt.clone()), &board.neighbor_cells(&pt, hood));
+            }
+        });
+    Board::from_cells(board.width(), cells).with_topology(board.topology())
+}
+
+/// Rule for the range 1 neighborhood in B/S notation, stored as a 512 entry
+/// table keyed on the 3x3 block around a cell (see [`key`]) which says if the
+/// cell is alive in the next generation.
+///
+/// Counts may carry isotropic non-totalistic (Hensel) letters which pick out
+/// single neighbor configurations, `B2-a/S12` is born with two neighbors
+/// unless they are adjacent, outer-totalistic rules like `B3/S23` simply set
+/// every configuration of a count.
+///
+/// Rules with more than two states are from the Generations family: a live
+/// cell which fails to survive passes through `states - 2` dying states
+/// before it becomes dead, dying cells do not count as neighbors and cannot
+/// be born into.
+///
+/// A trailing `H` or `V` selects the hexagonal (6 cell) or von Neumann
+/// (4 cell) neighborhood in place of the default Moore one
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LifeRule {
+    table: [u64; 8],
+    states: u8,
+    neighborhood: Neighborhood,
+}
+
+const MAX_NEIGHBORS: u32 = 8;
+const CENTER: u16 = 1 << 4;
+
+/// Hensel letters for one to four neighbors, each with one of the
+/// neighborhoods it stands for. The rest are found by rotating and
+/// reflecting, and five to seven neighbors use the complements of three to one
+const LETTERS: [&[(char, u16)]; 5] = [
+    &[],
+    &[('c', 0x001), ('e', 0x002)],
+    &[
+        ('c', 0x005),
+        ('e', 0x00a),
+        ('k', 0x021),
+        ('a', 0x003),
+        ('i', 0x028),
+        ('n', 0x044),
+    ],
+    &[
+        ('c', 0x045),
+        ('e', 0x02a),
+        ('k', 0x062),
+        ('a', 0x00b),
+        ('i', 0x007),
+        ('n', 0x00d),
+        ('y', 0x061),
+        ('q', 0x046),
+        ('j', 0x00e),
+        ('r', 0x029),
+    ],
+    &[
+        ('c', 0x145),
+        ('e', 0x0aa),
+        ('k', 0x063),
+        ('a', 0x00f),
+        ('i', 0x02d),
+        ('n', 0x047),
+        ('y', 0x065),
+        ('q', 0x066),
+        ('j', 0x06a),
+        ('r', 0x02b),
+        ('t', 0x069),
+        ('w', 0x04e),
+        ('z', 0x06c),
+    ],
+];
+
+/// Index into [`LifeRule`]'s table for a cell, bit `3 * y + x` is set when the
+/// cell at `(x, y)` of the 3x3 block around it is alive. `neighbors` is in
+/// the reading order of [`Board::neighbors`](crate::gol::Board::neighbors)
+pub fn key(alive: bool, neighbors: &[bool; 8]) -> u16 {
+    neighbors
+        .iter()
+        .enumerate()
+        .filter(|(_, b)| **b)
+        .fold((alive as u16) << 4, |k, (i, _)| {
+            k | 1 << if i < 4 { i } else { i + 1 }
+        })
+}
+
+/// Keys for every neighbor configuration with `count` live cells
+fn with_count(count: u32) -> impl Iterator<Item = u16> {
+    (0..512u16).filter(move |k| k & CENTER == 0 && k.count_ones() == count)
+}
+
+fn transform(key: u16, f: impl Fn(u16, u16) -> (u16, u16)) -> u16 {
+    (0..9).filter(|i| key & (1 << i) != 0).fold(0, |k, i| {
+        let (x, y) = f(i % 3, i / 3);
+        k | 1 << (3 * y + x)
+    })
+}
+
+/// Every neighborhood a Hensel letter stands for
+fn letter_keys(count: u32, letter: char) -> Option<Vec<u16>> {
+    let (_, base) = LETTERS[count.min(MAX_NEIGHBORS - count) as usize]
+        .iter()
+        .find(|(l, _)| *l == letter)?;
+    let base = if count > 4 { !base & 0x1ef } else { *base };
+    let mut keys: Vec<u16> = (0..4)
+        .scan(base, |k, _| {
+            *k = transform(*k, |x, y| (2 - y, x));
+            Some([*k, transform(*k, |x, y| (2 - x, y))])
+        })
+        .flatten()
+        .collect();
+    keys.sort_unstable();
+    keys.dedup();
+    Some(keys)
+}
+
+/// Parse the counts of a B or S section into the neighbor configurations
+/// they select
+fn parse_section(s: &str, hood: Neighborhood) -> Result<Vec<u16>> {
+    let max = hood.size(1) - 1;
+    let mut keys = Vec::new();
+    let mut cs = s.chars().map(|c| c.to_ascii_lowercase()).peekable();
+    while let Some(c) = cs.next() {
+        let n = c
+            .to_digit(10)
+            .ok_or_else(|| anyhow!("unexpected character '{}' in rulestring", c))?;
+        ensure!(
+            n <= max,
+            "neighbor count {} out of range, must be 0-{}",
+            n,
+            max
+        );
+        let negate = cs.next_if_eq(&'-').is_some();
+        let letters: Vec<char> =
+            std::iter::from_fn(|| cs.next_if(char::is_ascii_lowercase)).collect();
+        ensure!(
+            !negate || !letters.is_empty(),
+            "'-' after {} must be followed by letters",
+            n
+        );
+        if letters.is_empty() {
+            keys.extend(with_count(n));
+            continue;
+        }
+        ensure!(
+            hood == Neighborhood::Moore,
+            "non-totalistic letters need the Moore neighborhood"
+        );
+        let picked = letters
+            .iter()
+            .map(|l| {
+                letter_keys(n, *l)
+                    .ok_or_else(|| anyhow!("'{}' is not a valid letter for {} neighbors", l, n))
+            })
+            .collect::<Result<Vec<_>>>()?
+            .concat();
+        if negate {
+            keys.extend(with_count(n).filter(|k| !picked.contains(k)));
+        } else {
+            keys.extend(picked);
+        }
+    }
+    Ok(keys)
+}
+
+fn parse_states(s: &str) -> Result<u8> {
+    let states: u8 = s
+        .parse()
+        .map_err(|_| anyhow!("invalid number of states '{}'", s))?;
+    ensure!(states >= 2, "rules need at least 2 states, got {}", states);
+    Ok(states)
+}
+
+impl LifeRule {
+    pub fn life() -> Self {
+        let mut rule = Self {
+            table: [0; 8],
+            states: 2,
+            neighborhood: Neighborhood::Moore,
+        };
+        rule.set_all(with_count(3));
+        rule.set_all(with_count(2).chain(with_count(3)).map(|k| k | CENTER));
+        rule
+    }
+    pub fn states(&self) -> u8 {
+        self.states
+    }
+    pub fn neighborhood(&self) -> Neighborhood {
+        self.neighborhood
+    }
+    fn set_all(&mut self, keys: impl Iterator<Item = u16>) {
+        for k in keys {
+            self.table[k as usize / 64] |= 1 << (k % 64);
+        }
+    }
+    /// Whether the center of the 3x3 block `key` is alive next generation
+    pub fn get(&self, key: u16) -> bool {
+        let key = key & self.hood_mask();
+        self.table[key as usize / 64] & (1 << (key % 64)) != 0
+    }
+    /// Bits of a key which are part of the neighborhood, the others are
+    /// ignored by [`LifeRule::get`]
+    fn hood_mask(&self) -> u16 {
+        match self.neighborhood {
+            Neighborhood::VonNeumann => 0o272,
+            Neighborhood::Hexagonal => 0o673,
+            _ => 0o777,
+        }
+    }
+    /// Born with any configuration of `neighbors` live cells
+    pub fn born(&self, neighbors: u32) -> bool {
+        with_count(neighbors).any(|k| self.get(k))
+    }
+    /// Survives with any configuration of `neighbors` live cells
+    pub fn survives(&self, neighbors: u32) -> bool {
+        with_count(neighbors).any(|k| self.get(k | CENTER))
+    }
+    /// Next state of a cell in `state` (0 dead, 1 alive, 2.. dying) with
+    /// the given neighbors
+    pub fn next(&self, state: u8, neighbors: &[bool; 8]) -> u8 {
+        match state {
+            0 => self.get(key(false, neighbors)) as u8,
+            1 if self.get(key(true, neighbors)) => 1,
+            s if s + 1 < self.states => s + 1,
+            _ => 0,
+        }
+    }
+    fn fmt_section(&self, f: &mut std::fmt::Formatter<'_>, center: u16) -> std::fmt::Result {
+        let max = self.neighborhood.size(1) - 1;
+        for n in 0..=max {
+            let (on, off): (Vec<_>, Vec<_>) = with_count(n)
+                .filter(|k| self.within_hood(*k))
+                .partition(|k| self.get(k | center));
+            if on.is_empty() {
+                continue;
+            }
+            write!(f, "{}", n)?;
+            if off.is_empty() {
+                continue;
+            }
+            let letters = LETTERS[n.min(MAX_NEIGHBORS - n) as usize];
+            let (on, off): (Vec<_>, Vec<_>) = letters
+                .iter()
+                .map(|(l, _)| *l)
+                .partition(|l| letter_keys(n, *l).unwrap().iter().all(|k| on.contains(k)));
+            if on.len() <= off.len() {
+                on.iter().try_for_each(|l| write!(f, "{}", l))?;
+            } else {
+                write!(f, "-")?;
+                off.iter().try_for_each(|l| write!(f, "{}", l))?;
+            }
+        }
+        Ok(())
+    }
+    /// Whether every live cell of `key` is part of the neighborhood
+    fn within_hood(&self, key: u16) -> bool {
+        (0..9).filter(|i| key & 1 << i != 0).all(|i| {
+            let d = Point {
+                x: (i % 3) as i64 - 1,
+                y: (i / 3) as i64 - 1,
+            };
+            self.neighborhood.contains(1, &d)
+        })
+    }
+    /// Birth and survival conditions as bitsets of neighbor counts, if the
+    /// rule is outer-totalistic
+    pub fn totalistic(&self) -> Option<(u16, u16)> {
+        let max = self.neighborhood.size(1) - 1;
+        (0..=max).try_fold((0, 0), |(birth, survive), n| {
+            let keys: Vec<u16> = with_count(n).filter(|k| self.within_hood(*k)).collect();
+            let bit = |center: u16| match keys.iter().filter(|k| self.get(*k | center)).count() {
+                0 => Some(0),
+                c if c == keys.len() => Some(1 << n),
+                _ => None,
+            };
+            Some((birth | bit(0)?, survive | bit(CENTER)?))
+        })
+    }
+}
+
+/// Life-like and Generations rules read states as codes, only live cells
+/// (1) count as neighbors
+impl Rule for LifeRule {
+    type State = u8;
+
+    fn neighborhood(&self) -> Neighborhood {
+        self.neighborhood
+    }
+    fn next(&self, cell: u8, neighbors: &[u8; 8]) -> u8 {
+        LifeRule::next(self, cell, &neighbors.map(|s| s == 1))
+    }
+    fn palette(&self) -> Palette {
+        Palette::generations(self.states)
+    }
+}
+
+impl Default for LifeRule {
+    fn default() -> Self {
+        Self::life()
+    }
+}
+
+impl FromStr for LifeRule {
+    type Err = Error;
+
+    /// Accepts `B3/S23` (case insensitive, with or without the slash) as well
+    /// as the older `23/3` survival/birth form. Generations rules take a
+    /// third section with the number of states, `B2/S345/C4` or `345/2/4`
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        ensure!(!s.is_empty(), "empty rulestring");
+        let upper = s.to_ascii_uppercase();
+        let (upper, neighborhood) = match upper.strip_suffix(['H', 'V']) {
+            Some(rest) if upper.ends_with('H') => (rest, Neighborhood::Hexagonal),
+            Some(rest) => (rest, Neighborhood::VonNeumann),
+            None => (upper.as_str(), Neighborhood::Moore),
+        };
+        let (birth, survive, states) = parse_sections(s, upper)?;
+        let mut rule = Self {
+            table: [0; 8],
+            states,
+            neighborhood,
+        };
+        rule.set_all(parse_section(birth, neighborhood)?.into_iter());
+        rule.set_all(
+            parse_section(survive, neighborhood)?
+                .into_iter()
+                .map(|k| k | CENTER),
+        );
+        Ok(rule)
+    }
+}
+
+/// Split a rulestring into its birth, survival and number of states
+/// sections
+fn parse_sections<'a>(s: &str, upper: &'a str) -> Result<(&'a str, &'a str, u8)> {
+    if upper.starts_with('B') || upper.starts_with('S') {
+        let mut birth = None;
+        let mut survive = None;
+        let mut states = None;
+        for part in upper.split('/').flat_map(|p| {
+            // also handle the slashless B3S23 form
+            match p.find('S').filter(|i| *i > 0 && p.starts_with('B')) {
+                Some(i) => vec![&p[..i], &p[i..]],
+                None => vec![p],
+            }
+        }) {
+            match part.split_at(part.len().min(1)) {
+                ("B", cs) if birth.is_none() => birth = Some(cs),
+                ("S", cs) if survive.is_none() => survive = Some(cs),
+                ("C" | "G", n) if states.is_none() => states = Some(parse_states(n)?),
+                (d, _) if states.is_none() && d.chars().all(|c| c.is_ascii_digit()) => {
+                    states = Some(parse_states(part)?)
+                }
+                ("B" | "S" | "C" | "G", _) => {
+                    return Err(anyhow!("duplicate section in rule '{}'", s))
+                }
+                _ => return Err(anyhow!("expected B, S or C section in rule '{}'", s)),
+            }
+        }
+        Ok((
+            birth.ok_or_else(|| anyhow!("rule '{}' has no B section", s))?,
+            survive.ok_or_else(|| anyhow!("rule '{}' has no S section", s))?,
+            states.unwrap_or(2),
+        ))
+    } else {
+        let mut parts = upper.split('/');
+        let (survive, birth, states) = (parts.next(), parts.next(), parts.next());
+        ensure!(
+            birth.is_some() && parts.next().is_none(),
+            "rule '{}' is not in B/S or S/B notation",
+            s
+        );
+        Ok((
+            birth.unwrap_or_default(),
+            survive.unwrap_or_default(),
+            states.map(parse_states).transpose()?.unwrap_or(2),
+        ))
+    }
+}
+
+impl Display for LifeRule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "B")?;
+        self.fmt_section(f, 0)?;
+        write!(f, "/S")?;
+        self.fmt_section(f, CENTER)?;
+        if self.states > 2 {
+            write!(f, "/C{}", self.states)?;
+        }
+        match self.neighborhood {
+            Neighborhood::Hexagonal => write!(f, "H"),
+            Neighborhood::VonNeumann => write!(f, "V"),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Any of the supported rule families, as selected with `--rule`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AnyRule {
+    Life(LifeRule),
+    Ltl(LtlRule),
+    Wireworld(Wireworld),
+}
+
+impl AnyRule {
+    pub fn states(&self) -> u8 {
+        match self {
+            AnyRule::Life(r) => r.states(),
+            AnyRule::Ltl(r) => r.states(),
+            AnyRule::Wireworld(_) => Wireworld::STATES,
+        }
+    }
+    pub fn neighborhood(&self) -> Neighborhood {
+        match self {
+            AnyRule::Life(r) => r.neighborhood(),
+            AnyRule::Ltl(r) => r.neighborhood(),
+            AnyRule::Wireworld(r) => r.neighborhood(),
+        }
+    }
+    /// How the rule's states are drawn
+    pub fn palette(&self) -> Palette {
+        match self {
+            AnyRule::Life(r) => r.palette(),
+            AnyRule::Ltl(r) => Palette::generations(r.states()),
+            AnyRule::Wireworld(r) => r.palette(),
+        }
+    }
+}
+
+impl Default for AnyRule {
+    fn default() -> Self {
+        AnyRule::Life(LifeRule::life())
+    }
+}
+
+impl FromStr for AnyRule {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut cs = s.trim().chars();
+        if s.trim().eq_ignore_ascii_case("wireworld") {
+            Ok(AnyRule::Wireworld(Wireworld))
+        } else if matches!(cs.next(), Some('R' | 'r'))
+            && cs.next().is_some_and(|c| c.is_ascii_digit())
+        {
+            Ok(AnyRule::Ltl(s.parse()?))
+        } else {
+            Ok(AnyRule::Life(s.parse()?))
+        }
+    }
+}
+
+impl Display for AnyRule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AnyRule::Life(r) => r.fmt(f),
+            AnyRule::Ltl(r) => r.fmt(f),
+            AnyRule::Wireworld(_) => write!(f, "Wireworld"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_bs() -> Result<()> {
+        let r: LifeRule = "B36/S23".parse()?;
+        assert!(r.born(3) && r.born(6));
+        assert!(!r.born(2));
+        assert!(r.survives(2) && r.survives(3));
+        assert!(!r.survives(4));
+        assert_eq!(r.to_string(), "B36/S23");
+        Ok(())
+    }
+    #[test]
+    fn test_parse_forms_agree() -> Result<()> {
+        let life = LifeRule::life();
+        for s in ["B3/S23", "b3/s23", "S23/B3", "B3S23", "23/3"] {
+            assert_eq!(s.parse::<LifeRule>()?, life, "parsing {}", s);
+        }
+        Ok(())
+    }
+    #[test]
+    fn test_empty_sections() -> Result<()> {
+        let seeds: LifeRule = "B2/S".parse()?;
+        assert!(seeds.born(2));
+        assert!((0..=8).all(|n| !seeds.survives(n)));
+        assert_eq!("/2".parse::<LifeRule>()?, seeds);
+        Ok(())
+    }
+    #[test]
+    fn test_invalid() {
+        for s in [
+            "",
+            "B9/S23",
+            "B3/X23",
+            "B3",
+            "B3/S2x",
+            "B3/S2-",
+            "B0c/S23",
+            "B2a/S23H",
+            "B3/B3/S23",
+            "323",
+            "B2/S/C1",
+            "B2/S/C",
+            "B2/S/C3/C3",
+            "2/3/4/5",
+        ] {
+            assert!(s.parse::<LifeRule>().is_err(), "accepted {}", s);
+        }
+    }
+    #[test]
+    fn test_parse_generations() -> Result<()> {
+        let star_wars: LifeRule = "B2/S345/C4".parse()?;
+        assert_eq!(star_wars.states(), 4);
+        assert_eq!(star_wars.to_string(), "B2/S345/C4");
+        assert_eq!("345/2/4".parse::<LifeRule>()?, star_wars);
+        assert_eq!("B2/S345/4".parse::<LifeRule>()?, star_wars);
+        assert_eq!("B3/S23/C2".parse::<LifeRule>()?, LifeRule::life());
+        Ok(())
+    }
+    #[test]
+    fn test_parse_neighborhood_suffix() -> Result<()> {
+        let hex: LifeRule = "B2/S34H".parse()?;
+        assert_eq!(hex.neighborhood(), Neighborhood::Hexagonal);
+        assert!(hex.born(2) && hex.survives(4));
+        assert_eq!(hex.to_string(), "B2/S34H");
+        let vn: LifeRule = "b1/s013v".parse()?;
+        assert_eq!(vn.neighborhood(), Neighborhood::VonNeumann);
+        assert_eq!(vn.to_string(), "B1/S013V");
+        assert_eq!("B2/S34/C3H".parse::<LifeRule>()?.states(), 3);
+        assert!("B7/S23H".parse::<LifeRule>().is_err());
+        assert!("B3/S5V".parse::<LifeRule>().is_err());
+        assert!("B3/S23HV".parse::<LifeRule>().is_err());
+        Ok(())
+    }
+    #[test]
+    fn test_any_rule_dispatch() -> Result<()> {
+        assert!(matches!("B3/S23".parse()?, AnyRule::Life(_)));
+        assert!(matches!(
+            "R5,C0,M1,S34..58,B34..45,NM".parse()?,
+            AnyRule::Ltl(_)
+        ));
+        assert!("R".parse::<AnyRule>().is_err());
+        let wireworld: AnyRule = "WireWorld".parse()?;
+        assert_eq!(wireworld.to_string().parse::<AnyRule>()?, wireworld);
+        assert_eq!(wireworld.states(), 4);
+        Ok(())
+    }
+    #[test]
+    fn test_generic_step() -> Result<()> {
+        // brain's brain: a live pair leaves dying cells behind
+        let brain: LifeRule = "B2/S/C3".parse()?;
+        let mut board = Board::empty(6, 6);
+        board.set(Point { x: 2, y: 2 }, true);
+        board.set(Point { x: 3, y: 2 }, true);
+        let next = step(&board, &brain);
+        assert_eq!(next.state(Point { x: 2, y: 2 }), 2);
+        assert_eq!(next.alive(), 4);
+        assert_eq!(next.topology(), board.topology());
+        Ok(())
+    }
+    fn first(n: usize) -> [bool; 8] {
+        let mut out = [false; 8];
+        out[..n].fill(true);
+        out
+    }
+    #[test]
+    fn test_generations_decay() -> Result<()> {
+        let brain: LifeRule = "B2/S/C3".parse()?;
+        assert_eq!(brain.next(0, &first(2)), 1);
+        assert_eq!(brain.next(0, &first(3)), 0);
+        // live cells never survive, they go through the single dying state
+        assert_eq!(brain.next(1, &first(2)), 2);
+        assert_eq!(brain.next(2, &first(2)), 0);
+
+        let star_wars: LifeRule = "B2/S345/C4".parse()?;
+        assert_eq!(star_wars.next(1, &first(4)), 1);
+        assert_eq!(star_wars.next(1, &first(1)), 2);
+        assert_eq!(star_wars.next(2, &first(2)), 3);
+        assert_eq!(star_wars.next(3, &first(2)), 0);
+        Ok(())
+    }
+    #[test]
+    fn test_letters_partition_counts() {
+        for n in 0..=MAX_NEIGHBORS {
+            let letters = LETTERS[n.min(MAX_NEIGHBORS - n) as usize];
+            let mut keys: Vec<u16> = letters
+                .iter()
+                .flat_map(|(l, _)| letter_keys(n, *l).unwrap())
+                .collect();
+            keys.sort_unstable();
+            if letters.is_empty() {
+                assert_eq!(with_count(n).count(), 1);
+            } else {
+                assert_eq!(keys, with_count(n).collect::<Vec<_>>(), "count {}", n);
+            }
+        }
+    }
+    #[test]
+    fn test_parse_hensel() -> Result<()> {
+        let r: LifeRule = "B2-a/S12".parse()?;
+        // NW and N are adjacent, NW and NE are not
+        let adjacent = [true, true, false, false, false, false, false, false];
+        let apart = [true, false, true, false, false, false, false, false];
+        assert_eq!(r.next(0, &adjacent), 0);
+        assert_eq!(r.next(0, &apart), 1);
+        assert_eq!(r.to_string(), "B2-a/S12");
+
+        let tlife: LifeRule = "B3/S2-i34q".parse()?;
+        assert_eq!(tlife.to_string(), "B3/S2-i34q");
+        // W and E are opposite
+        let opposite = [false, false, false, true, true, false, false, false];
+        assert_eq!(tlife.next(1, &opposite), 0);
+        assert_eq!(tlife.next(1, &adjacent), 1);
+        assert_eq!(
+            "b2ce3-k/s23".parse::<LifeRule>()?.to_string(),
+            "B2ce3-k/S23"
+        );
+        Ok(())
+    }
+    #[test]
+    fn test_get_ignores_cells_outside_hood() -> Result<()> {
+        let hex: LifeRule = "B2/S34H".parse()?;
+        // NW and N, plus the NE corner which hexagonal rules ignore
+        assert!(hex.get(0b011));
+        assert!(hex.get(0b111));
+        let vn: LifeRule = "B2/SV".parse()?;
+        assert!(!vn.get(0b011));
+        assert!(vn.get(0b1010));
+        Ok(())
+    }
+    #[test]
+    fn test_totalistic_counts() -> Result<()> {
+        assert_eq!(LifeRule::life().totalistic(), Some((1 << 3, 0b1100)));
+        assert_eq!(
+            "B2/S34H".parse::<LifeRule>()?.totalistic(),
+            Some((0b100, 0b11000))
+        );
+        assert_eq!("B2-a/S12".parse::<LifeRule>()?.totalistic(), None);
+        Ok(())
+    }
+    #[test]
+    fn test_totalistic_is_full_table() -> Result<()> {
+        let explicit: LifeRule = "B3cekainyqjr/S2cekain3".parse()?;
+        assert_eq!(explicit, LifeRule::life());
+        assert_eq!(explicit.to_string(), "B3/S23");
+        Ok(())
+    }
+}
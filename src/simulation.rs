@@ -0,0 +1,349 @@
+use anyhow::{bail, Result};
+use std::time::Duration;
+
+use crate::{
+    cycle::{Cycle, Detector},
+    gol::{Board, Point},
+    hashlife::{Universe, MAX_STEP},
+    ltl,
+    macrocell::Macrocell,
+    packed::{self, WordRule},
+    rule::{self, AnyRule},
+    sparse::Plane,
+    stats::Sample,
+    topology::Topology,
+};
+
+/// Cells after a step, either a whole board with its edges joined by its
+/// topology or an unbounded plane the viewport pans across
+#[derive(Clone)]
+pub enum World {
+    Bounded(Board),
+    Plane(Plane),
+}
+
+impl World {
+    pub fn observe(&self, detector: &mut Detector, generation: u64) -> Option<Cycle> {
+        match self {
+            World::Bounded(b) => detector.observe(b, generation),
+            World::Plane(p) => detector.observe(p, generation),
+        }
+    }
+    /// Stats of `generation`, with births and deaths counted from `prev`
+    /// when it's the generation just before
+    pub fn sample(&self, prev: Option<&World>, generation: u64, step_time: Duration) -> Sample {
+        let (population, changes, bounds) = match (self, prev) {
+            (World::Bounded(b), prev) => (
+                b.alive() as u64,
+                match prev {
+                    Some(World::Bounded(p)) => p.changes(b),
+                    _ => None,
+                },
+                b.bounds(),
+            ),
+            (World::Plane(p), prev) => (
+                p.population(),
+                match prev {
+                    Some(World::Plane(q)) => Some(q.changes(p)),
+                    _ => None,
+                },
+                p.bounds(),
+            ),
+        };
+        Sample {
+            generation,
+            population,
+            changes,
+            bounds,
+            step_time,
+        }
+    }
+    /// Every live cell on a board, a plane is cut down to a board with dead
+    /// edges around its pattern
+    pub fn board(&self) -> Board {
+        match self {
+            World::Bounded(b) => b.clone(),
+            World::Plane(p) => {
+                let (min, max) = p
+                    .bounds()
+                    .unwrap_or((Point { x: 0, y: 0 }, Point { x: 0, y: 0 }));
+                let corner = min.clone() + Point { x: -2, y: -2 };
+                let (w, h) = (max.x - min.x + 5, max.y - min.y + 5);
+                p.window(&corner, w as u32, h as u32)
+                    .with_topology(Topology::Plane)
+            }
+        }
+    }
+}
+
+/// What a [`Simulation`] steps its cells with
+enum Engine {
+    Board(Board),
+    /// Hashlife, looked at through a window the size of the starting board
+    Hashlife {
+        universe: Universe,
+        corner: Point,
+        width: u32,
+        height: u32,
+    },
+    Plane(Plane),
+}
+
+/// A pattern being run under a rule, one of the engines picked by its
+/// constructor does the stepping
+pub struct Simulation {
+    engine: Engine,
+    rule: AnyRule,
+    generation: u64,
+    /// Generations a hashlife step advances, as a power of two
+    step: u8,
+}
+
+impl Simulation {
+    /// Simulation of `board`, whose edges are joined by its topology
+    pub fn new(board: Board, rule: AnyRule) -> Self {
+        Self {
+            engine: Engine::Board(board),
+            rule,
+            generation: 0,
+            step: 0,
+        }
+    }
+    /// Simulation of `board` run with hashlife on an unbounded plane, seen
+    /// through a window the size of `board`
+    pub fn hashlife(board: &Board, rule: AnyRule) -> Result<Self> {
+        let AnyRule::Life(r) = &rule else {
+            bail!("hashlife only supports B/S rules");
+        };
+        let engine = Engine::Hashlife {
+            universe: Universe::from_board(board, r)?,
+            corner: Point { x: 0, y: 0 },
+            width: board.width(),
+            height: board.height(),
+        };
+        Ok(Self {
+            engine,
+            rule,
+            generation: 0,
+            step: 0,
+        })
+    }
+    /// Simulation of `mc` run with hashlife, seen through a `width` by
+    /// `height` window over the middle of its live cells
+    pub fn from_macrocell(mc: &Macrocell, rule: AnyRule, width: u32, height: u32) -> Result<Self> {
+        let AnyRule::Life(r) = &rule else {
+            bail!("hashlife only supports B/S rules");
+        };
+        let (min, max) = mc
+            .bounds()
+            .unwrap_or((Point { x: 0, y: 0 }, Point { x: 0, y: 0 }));
+        let corner = Point {
+            x: min.x + (max.x - min.x) / 2 - width as i64 / 2,
+            y: min.y + (max.y - min.y) / 2 - height as i64 / 2,
+        };
+        let engine = Engine::Hashlife {
+            universe: Universe::from_macrocell(mc, r)?,
+            corner,
+            width,
+            height,
+        };
+        Ok(Self {
+            engine,
+            rule,
+            generation: 0,
+            step: 0,
+        })
+    }
+    /// Simulation of `board` placed on an unbounded plane
+    pub fn unbounded(board: &Board, rule: AnyRule) -> Result<Self> {
+        let AnyRule::Life(r) = &rule else {
+            bail!("the unbounded plane only supports B/S rules");
+        };
+        Ok(Self {
+            engine: Engine::Plane(Plane::from_board(board, r)?),
+            rule,
+            generation: 0,
+            step: 0,
+        })
+    }
+    pub fn rule(&self) -> &AnyRule {
+        &self.rule
+    }
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+    /// Whether hashlife is doing the stepping, so a step may skip
+    /// generations
+    pub fn is_hashlife(&self) -> bool {
+        matches!(self.engine, Engine::Hashlife { .. })
+    }
+    /// Make each [`Simulation::step`] advance `2^k` generations when running
+    /// with hashlife, the other engines always advance one
+    pub fn set_step(&mut self, k: u8) {
+        self.step = k;
+    }
+    /// Advance one step and return the generation reached
+    pub fn step(&mut self) -> u64 {
+        match &mut self.engine {
+            Engine::Hashlife { universe, .. } => {
+                universe.step(self.step);
+                self.generation = universe.generation();
+            }
+            Engine::Plane(p) => {
+                p.step();
+                self.generation += 1;
+            }
+            Engine::Board(b) => {
+                let board = std::mem::replace(b, Board::empty(0, 0));
+                *b = next_board(board, &self.rule);
+                self.generation += 1;
+            }
+        }
+        self.generation
+    }
+    /// Advance `n` generations, hashlife covers them in as few steps as it
+    /// can, those past its largest step taking several of them
+    pub fn step_n(&mut self, n: u64) -> u64 {
+        if let Engine::Hashlife { universe, .. } = &mut self.engine {
+            for _ in 0..n >> MAX_STEP {
+                universe.step(MAX_STEP);
+            }
+            for k in (0..MAX_STEP).rev().filter(|k| n & (1 << k) != 0) {
+                universe.step(k);
+            }
+            self.generation = universe.generation();
+        } else {
+            for _ in 0..n {
+                self.step();
+            }
+        }
+        self.generation
+    }
+    /// Cells as they are now
+    pub fn world(&self) -> World {
+        match &self.engine {
+            Engine::Board(b) => World::Bounded(b.clone()),
+            Engine::Hashlife {
+                universe,
+                corner,
+                width,
+                height,
+            } => World::Bounded(universe.window(corner, *width, *height)),
+            Engine::Plane(p) => World::Plane(p.clone()),
+        }
+    }
+    /// Endless iterator which steps the simulation and yields each
+    /// generation reached along with its cells
+    pub fn generations(&mut self) -> Generations<'_> {
+        Generations(self)
+    }
+    /// Every live cell as a macrocell quadtree, the whole universe rather
+    /// than just the window when running with hashlife
+    pub fn macrocell(&self) -> Macrocell {
+        let mut mc = match &self.engine {
+            Engine::Hashlife { universe, .. } => universe.to_macrocell(),
+            _ => Macrocell::from_board(&self.world().board()),
+        };
+        mc.generation = self.generation;
+        mc
+    }
+}
+
+/// Iterator returned by [`Simulation::generations`]
+pub struct Generations<'a>(&'a mut Simulation);
+
+impl Iterator for Generations<'_> {
+    type Item = (u64, World);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let generation = self.0.step();
+        Some((generation, self.0.world()))
+    }
+}
+
+/// Next generation of a bounded board, with the packed and Larger than Life
+/// fast paths where they apply
+fn next_board(board: Board, rule: &AnyRule) -> Board {
+    let rule = match rule {
+        AnyRule::Life(r) => r,
+        AnyRule::Ltl(r) => return ltl::step(&board, r),
+        AnyRule::Wireworld(r) => return rule::step(&board.cast(), r).cast(),
+    };
+    if let Some(word_rule) =
+        WordRule::from_rule(rule).filter(|_| board.topology() == Topology::Torus)
+    {
+        return packed::step(&board, &word_rule);
+    }
+    rule::step(&board, rule)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn glider() -> Board {
+        let mut board = Board::empty(16, 16);
+        for (x, y) in [(1, 0), (2, 1), (0, 2), (1, 2), (2, 2)] {
+            board.set(Point { x, y }, true);
+        }
+        board
+    }
+    fn cells(world: &World) -> Vec<Point> {
+        let board = world.board();
+        let mut cells: Vec<_> = (0..board.height() as i64)
+            .flat_map(|y| (0..board.width() as i64).map(move |x| Point { x, y }))
+            .filter(|p| board[p.clone()])
+            .collect();
+        let min = cells.iter().map(|p| (p.x, p.y)).min().unwrap_or((0, 0));
+        for p in &mut cells {
+            p.x -= min.0;
+            p.y -= min.1;
+        }
+        cells
+    }
+
+    #[test]
+    fn test_engines_agree() -> Result<()> {
+        let mut bounded = Simulation::new(glider(), AnyRule::default());
+        let mut hashlife = Simulation::hashlife(&glider(), AnyRule::default())?;
+        let mut plane = Simulation::unbounded(&glider(), AnyRule::default())?;
+        assert_eq!(bounded.step_n(6), 6);
+        assert_eq!(hashlife.step_n(6), 6);
+        assert_eq!(plane.step_n(6), 6);
+        let expected = cells(&bounded.world());
+        assert_eq!(cells(&hashlife.world()), expected);
+        assert_eq!(cells(&plane.world()), expected);
+        assert!(hashlife.is_hashlife() && !plane.is_hashlife());
+        Ok(())
+    }
+    #[test]
+    fn test_hashlife_steps_past_max_step() -> Result<()> {
+        let mut block = Board::empty(4, 4);
+        for (x, y) in [(1, 1), (2, 1), (1, 2), (2, 2)] {
+            block.set(Point { x, y }, true);
+        }
+        let mut sim = Simulation::hashlife(&block, AnyRule::default())?;
+        let n = (3 << MAX_STEP) + 5;
+        assert_eq!(sim.step_n(n), n);
+        assert_eq!(sim.generation(), n);
+        assert_eq!(sim.world().board().alive(), 4);
+        Ok(())
+    }
+    #[test]
+    fn test_generations() {
+        let mut sim = Simulation::new(glider(), AnyRule::default());
+        let populations: Vec<_> = sim
+            .generations()
+            .take(4)
+            .map(|(g, w)| (g, w.board().alive()))
+            .collect();
+        assert_eq!(populations, vec![(1, 5), (2, 5), (3, 5), (4, 5)]);
+        assert_eq!(sim.generation(), 4);
+    }
+    #[test]
+    fn test_rejects_unsupported_engines() {
+        let ltl: AnyRule = "R5,C0,M1,S34..58,B34..45,NM".parse().unwrap();
+        assert!(Simulation::hashlife(&glider(), ltl.clone()).is_err());
+        assert!(Simulation::unbounded(&glider(), ltl).is_err());
+    }
+}
@@ -0,0 +1,353 @@
+use anyhow::{anyhow, bail, ensure, Error, Result};
+use std::{fmt::Display, str::FromStr};
+
+use crate::{
+    gol::{Board, Point},
+    macrocell::MAX_BOARD,
+};
+
+/// SplitMix64, small and fast with a fixed output for every seed so soups
+/// can be reproduced from their seed alone
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+    /// `true` with probability `p`
+    pub fn chance(&mut self, p: f64) -> bool {
+        ((self.next_u64() >> 11) as f64) / ((1u64 << 53) as f64) < p
+    }
+}
+
+/// Width and height given as `WxH`
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Size {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl FromStr for Size {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (w, h) = s
+            .split_once(['x', 'X'])
+            .ok_or_else(|| anyhow!("expected WxH, got '{}'", s))?;
+        let size = Self {
+            width: w.trim().parse()?,
+            height: h.trim().parse()?,
+        };
+        ensure!(size.width > 0 && size.height > 0, "empty soup size '{}'", s);
+        ensure!(
+            size.width as u64 * size.height as u64 <= MAX_BOARD,
+            "{} is too large for a board",
+            size
+        );
+        Ok(size)
+    }
+}
+
+impl Display for Size {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}x{}", self.width, self.height)
+    }
+}
+
+/// Symmetry of a soup, named as in apgsearch. The digit after the group
+/// says where its center is: on a cell (1), on the edge between two cells
+/// (2) or on the corner between four (4), which fixes whether the soup's
+/// sides are odd or even
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Symmetry {
+    #[default]
+    C1,
+    C2_1,
+    C2_2,
+    C2_4,
+    C4_1,
+    C4_4,
+    D2Plus1,
+    D2Plus2,
+    D2X,
+    D4Plus1,
+    D4Plus2,
+    D4Plus4,
+    D4X1,
+    D4X4,
+    D8_1,
+    D8_4,
+}
+
+const NAMES: [(Symmetry, &str); 16] = [
+    (Symmetry::C1, "C1"),
+    (Symmetry::C2_1, "C2_1"),
+    (Symmetry::C2_2, "C2_2"),
+    (Symmetry::C2_4, "C2_4"),
+    (Symmetry::C4_1, "C4_1"),
+    (Symmetry::C4_4, "C4_4"),
+    (Symmetry::D2Plus1, "D2_+1"),
+    (Symmetry::D2Plus2, "D2_+2"),
+    (Symmetry::D2X, "D2_x"),
+    (Symmetry::D4Plus1, "D4_+1"),
+    (Symmetry::D4Plus2, "D4_+2"),
+    (Symmetry::D4Plus4, "D4_+4"),
+    (Symmetry::D4X1, "D4_x1"),
+    (Symmetry::D4X4, "D4_x4"),
+    (Symmetry::D8_1, "D8_1"),
+    (Symmetry::D8_4, "D8_4"),
+];
+
+/// Rotation or reflection about the middle of the soup, on coordinates
+/// doubled so the middle falls on a whole number
+#[derive(Copy, Clone, Debug)]
+enum Transform {
+    Identity,
+    Rotate90,
+    Rotate180,
+    Rotate270,
+    FlipX,
+    FlipY,
+    Diagonal,
+    AntiDiagonal,
+}
+
+impl Transform {
+    fn apply(self, (u, v): (i64, i64)) -> (i64, i64) {
+        match self {
+            Transform::Identity => (u, v),
+            Transform::Rotate90 => (-v, u),
+            Transform::Rotate180 => (-u, -v),
+            Transform::Rotate270 => (v, -u),
+            Transform::FlipX => (-u, v),
+            Transform::FlipY => (u, -v),
+            Transform::Diagonal => (v, u),
+            Transform::AntiDiagonal => (-v, -u),
+        }
+    }
+}
+
+/// Whether a side has to be odd or even, or either
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum Parity {
+    Odd,
+    Even,
+    Any,
+}
+
+impl Symmetry {
+    fn group(self) -> &'static [Transform] {
+        use Transform::*;
+        match self {
+            Symmetry::C1 => &[Identity],
+            Symmetry::C2_1 | Symmetry::C2_2 | Symmetry::C2_4 => &[Identity, Rotate180],
+            Symmetry::C4_1 | Symmetry::C4_4 => &[Identity, Rotate90, Rotate180, Rotate270],
+            Symmetry::D2Plus1 | Symmetry::D2Plus2 => &[Identity, FlipX],
+            Symmetry::D2X => &[Identity, Diagonal],
+            Symmetry::D4Plus1 | Symmetry::D4Plus2 | Symmetry::D4Plus4 => {
+                &[Identity, FlipX, FlipY, Rotate180]
+            }
+            Symmetry::D4X1 | Symmetry::D4X4 => &[Identity, Diagonal, AntiDiagonal, Rotate180],
+            Symmetry::D8_1 | Symmetry::D8_4 => &[
+                Identity,
+                Rotate90,
+                Rotate180,
+                Rotate270,
+                FlipX,
+                FlipY,
+                Diagonal,
+                AntiDiagonal,
+            ],
+        }
+    }
+    /// Parity the width and height must have and whether they must be equal,
+    /// for centers on an edge either side may be the even one
+    fn shape(self, size: Size) -> (Parity, Parity, bool) {
+        use Parity::*;
+        match self {
+            Symmetry::C1 => (Any, Any, false),
+            Symmetry::C2_1 | Symmetry::D4Plus1 => (Odd, Odd, false),
+            Symmetry::C2_2 | Symmetry::D4Plus2 if size.width % 2 == 1 => (Odd, Even, false),
+            Symmetry::C2_2 | Symmetry::D4Plus2 => (Even, Odd, false),
+            Symmetry::C2_4 | Symmetry::D4Plus4 => (Even, Even, false),
+            Symmetry::D2Plus1 => (Odd, Any, false),
+            Symmetry::D2Plus2 => (Even, Any, false),
+            Symmetry::D2X => (Any, Any, true),
+            Symmetry::C4_1 | Symmetry::D4X1 | Symmetry::D8_1 => (Odd, Odd, true),
+            Symmetry::C4_4 | Symmetry::D4X4 | Symmetry::D8_4 => (Even, Even, true),
+        }
+    }
+    /// Check a `size` soup can have this symmetry
+    pub fn check(self, size: Size) -> Result<()> {
+        let (pw, ph, square) = self.shape(size);
+        let fits = |p, n: u32| p == Parity::Any || (p == Parity::Odd) == (n % 2 == 1);
+        ensure!(
+            fits(pw, size.width) && fits(ph, size.height),
+            "{} soups must be {} wide and {} high, got {}",
+            self,
+            describe(pw),
+            describe(ph),
+            size
+        );
+        ensure!(
+            !square || size.width == size.height,
+            "{} soups must be square, got {}",
+            self,
+            size
+        );
+        Ok(())
+    }
+}
+
+fn describe(p: Parity) -> &'static str {
+    match p {
+        Parity::Odd => "an odd number of cells",
+        Parity::Even => "an even number of cells",
+        Parity::Any => "any number of cells",
+    }
+}
+
+impl FromStr for Symmetry {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match NAMES.iter().find(|(_, name)| name.eq_ignore_ascii_case(s)) {
+            Some((sym, _)) => Ok(*sym),
+            None => bail!("unknown symmetry '{}'", s),
+        }
+    }
+}
+
+impl Display for Symmetry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (_, name) = NAMES.iter().find(|(sym, _)| sym == self).unwrap();
+        write!(f, "{}", name)
+    }
+}
+
+/// Random `size` soup with each cell (or each set of cells the symmetry
+/// ties together) alive with probability `density`
+pub fn generate(size: Size, density: f64, symmetry: Symmetry, seed: u64) -> Result<Board> {
+    ensure!(
+        (0.0..=1.0).contains(&density),
+        "density must be between 0 and 1, got {}",
+        density
+    );
+    symmetry.check(size)?;
+    let (w, h) = (size.width as i64, size.height as i64);
+    let mut rng = Rng::new(seed);
+    let mut board = Board::empty(size.width, size.height);
+    for y in 0..h {
+        for x in 0..w {
+            // the first cell of an orbit in reading order picks its state,
+            // the rest copy it
+            let doubled = (2 * x - (w - 1), 2 * y - (h - 1));
+            let first = symmetry
+                .group()
+                .iter()
+                .map(|t| {
+                    let (u, v) = t.apply(doubled);
+                    ((v + h - 1) / 2, (u + w - 1) / 2)
+                })
+                .min()
+                .unwrap();
+            let alive = if first == (y, x) {
+                rng.chance(density)
+            } else {
+                board[Point {
+                    x: first.1,
+                    y: first.0,
+                }]
+            };
+            if alive {
+                board.set(Point { x, y }, true);
+            }
+        }
+    }
+    Ok(board)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn size(s: &str) -> Size {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_reproducible() -> Result<()> {
+        let a = generate(size("16x16"), 0.5, Symmetry::C1, 7)?;
+        let b = generate(size("16x16"), 0.5, Symmetry::C1, 7)?;
+        let c = generate(size("16x16"), 0.5, Symmetry::C1, 8)?;
+        assert_eq!(a.states(), b.states());
+        assert_ne!(a.states(), c.states());
+        let alive = generate(size("100x100"), 0.3, Symmetry::C1, 1)?.alive();
+        assert!((2700..3300).contains(&alive), "{}", alive);
+        assert_eq!(generate(size("8x8"), 0.0, Symmetry::C1, 1)?.alive(), 0);
+        assert_eq!(generate(size("8x8"), 1.0, Symmetry::C1, 1)?.alive(), 64);
+        Ok(())
+    }
+    #[test]
+    fn test_symmetric() -> Result<()> {
+        for (name, s) in [
+            ("C2_1", "15x9"),
+            ("C2_2", "16x9"),
+            ("C2_2", "9x16"),
+            ("C4_4", "16x16"),
+            ("D2_+1", "15x10"),
+            ("D2_x", "12x12"),
+            ("D4_+4", "16x10"),
+            ("D4_x1", "15x15"),
+            ("D8_1", "17x17"),
+            ("D8_4", "16x16"),
+        ] {
+            let symmetry: Symmetry = name.parse()?;
+            assert_eq!(symmetry.to_string(), name);
+            let board = generate(size(s), 0.5, symmetry, 3)?;
+            let (w, h) = (board.width() as i64, board.height() as i64);
+            for y in 0..h {
+                for x in 0..w {
+                    for t in symmetry.group() {
+                        let (u, v) = t.apply((2 * x - (w - 1), 2 * y - (h - 1)));
+                        let image = Point {
+                            x: (u + w - 1) / 2,
+                            y: (v + h - 1) / 2,
+                        };
+                        assert_eq!(board[Point { x, y }], board[image], "{}", name);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+    #[test]
+    fn test_shape_checked() {
+        for (name, s) in [
+            ("C2_1", "16x9"),
+            ("C4_4", "16x14"),
+            ("D8_1", "16x16"),
+            ("D2_+2", "15x4"),
+            ("C2_2", "16x16"),
+        ] {
+            let symmetry: Symmetry = name.parse().unwrap();
+            assert!(
+                generate(size(s), 0.5, symmetry, 1).is_err(),
+                "{} {}",
+                name,
+                s
+            );
+        }
+        assert!("3x".parse::<Size>().is_err());
+        assert!("200000x200000".parse::<Size>().is_err());
+        assert!("C3".parse::<Symmetry>().is_err());
+        assert!(generate(size("4x4"), 1.5, Symmetry::C1, 1).is_err());
+    }
+}
@@ -0,0 +1,290 @@
+use anyhow::{anyhow, ensure, Result};
+use rayon::prelude::*;
+use std::{
+    collections::{HashMap, HashSet},
+    hash::{Hash, Hasher},
+};
+
+use crate::{
+    gol::{Board, Point},
+    packed::WordRule,
+    rule::LifeRule,
+};
+
+/// Cells along each side of a tile, a tile row is a single word
+const TILE: i64 = 64;
+
+type Tile = [u64; TILE as usize];
+
+const EMPTY: Tile = [0; TILE as usize];
+
+/// Unbounded plane holding only the 64x64 tiles which have live cells in
+/// them, so it grows with the pattern instead of wrapping around like a
+/// [`Board`] does
+#[derive(Clone, Debug)]
+pub struct Plane {
+    /// Tiles keyed on their position in tile units, bit `x % 64` of row
+    /// `y % 64` being the cell at `(x, y)`
+    tiles: HashMap<(i64, i64), Tile>,
+    rule: WordRule,
+}
+
+fn tile_of(pt: &Point) -> ((i64, i64), usize, u32) {
+    (
+        (pt.x.div_euclid(TILE), pt.y.div_euclid(TILE)),
+        pt.y.rem_euclid(TILE) as usize,
+        pt.x.rem_euclid(TILE) as u32,
+    )
+}
+
+impl Plane {
+    pub fn new(rule: &LifeRule) -> Result<Self> {
+        let (birth, _) = rule
+            .totalistic()
+            .filter(|_| rule.states() == 2)
+            .ok_or_else(|| {
+                anyhow!(
+                    "the unbounded plane needs a two-state outer-totalistic rule, got {}",
+                    rule
+                )
+            })?;
+        ensure!(
+            birth & 1 == 0,
+            "{} fills every empty cell of the plane, B0 rules can't be unbounded",
+            rule
+        );
+        Ok(Self {
+            tiles: HashMap::new(),
+            rule: WordRule::from_rule(rule).expect("rule was already checked"),
+        })
+    }
+    /// Copy of `board` with its top left corner at the origin
+    pub fn from_board(board: &Board, rule: &LifeRule) -> Result<Self> {
+        let mut plane = Self::new(rule)?;
+        for y in 0..board.height() {
+            for (i, word) in board.row(y).iter().enumerate() {
+                let key = (i as i64, y as i64 / TILE);
+                if *word != 0 {
+                    plane.tiles.entry(key).or_insert(EMPTY)[(y as i64 % TILE) as usize] = *word;
+                }
+            }
+        }
+        Ok(plane)
+    }
+
+    pub fn get(&self, pt: &Point) -> bool {
+        let (key, row, bit) = tile_of(pt);
+        self.tiles
+            .get(&key)
+            .is_some_and(|tile| tile[row] & (1 << bit) != 0)
+    }
+    pub fn set(&mut self, pt: Point, alive: bool) {
+        let (key, row, bit) = tile_of(&pt);
+        if alive {
+            self.tiles.entry(key).or_insert(EMPTY)[row] |= 1 << bit;
+        } else if let Some(tile) = self.tiles.get_mut(&key) {
+            tile[row] &= !(1 << bit);
+            if *tile == EMPTY {
+                self.tiles.remove(&key);
+            }
+        }
+    }
+    pub fn population(&self) -> u64 {
+        self.tiles
+            .values()
+            .flatten()
+            .map(|w| w.count_ones() as u64)
+            .sum()
+    }
+    /// Every live cell, in no particular order
+    pub fn cells(&self) -> Vec<Point> {
+        self.tiles
+            .iter()
+            .flat_map(|((tx, ty), tile)| {
+                tile.iter().enumerate().flat_map(move |(y, word)| {
+                    (0..TILE)
+                        .filter(move |x| word & (1 << x) != 0)
+                        .map(move |x| Point {
+                            x: tx * TILE + x,
+                            y: ty * TILE + y as i64,
+                        })
+                })
+            })
+            .collect()
+    }
+    /// Top left and bottom right live cells of the smallest rectangle
+    /// holding the pattern, `None` if it has died out
+    pub fn bounds(&self) -> Option<(Point, Point)> {
+        let cells = self.cells();
+        let xs = cells.iter().map(|p| p.x);
+        let ys = cells.iter().map(|p| p.y);
+        Some((
+            Point {
+                x: xs.clone().min()?,
+                y: ys.clone().min()?,
+            },
+            Point {
+                x: xs.max()?,
+                y: ys.max()?,
+            },
+        ))
+    }
+    /// Cells which came alive and cells which stopped being alive going
+    /// from `self` to `next`
+    pub fn changes(&self, next: &Plane) -> (u64, u64) {
+        let count = |from: &Plane, to: &Plane| -> u64 {
+            from.tiles
+                .iter()
+                .map(|(key, tile)| {
+                    let other = to.tiles.get(key).unwrap_or(&EMPTY);
+                    tile.iter()
+                        .zip(other)
+                        .map(|(a, b)| (a & !b).count_ones() as u64)
+                        .sum::<u64>()
+                })
+                .sum()
+        };
+        (count(next, self), count(self, next))
+    }
+    /// Cells of the `width` by `height` rectangle with its top left corner
+    /// at `corner`
+    pub fn window(&self, corner: &Point, width: u32, height: u32) -> Board {
+        let mut board = Board::empty(width, height);
+        for y in 0..height as i64 {
+            for x in 0..width as i64 {
+                let pt = Point { x, y };
+                if self.get(&(corner.clone() + pt.clone())) {
+                    board.set(pt, true);
+                }
+            }
+        }
+        board
+    }
+
+    /// Advance the plane by one generation, only tiles with live cells in or
+    /// next to them are visited
+    pub fn step(&mut self) {
+        let candidates: HashSet<(i64, i64)> = self
+            .tiles
+            .keys()
+            .flat_map(|(tx, ty)| {
+                (-1..=1).flat_map(move |dy| (-1..=1).map(move |dx| (tx + dx, ty + dy)))
+            })
+            .collect();
+        self.tiles = candidates
+            .into_par_iter()
+            .filter_map(|key| {
+                let tile = self.next_tile(key);
+                (tile != EMPTY).then_some((key, tile))
+            })
+            .collect();
+    }
+    fn next_tile(&self, (tx, ty): (i64, i64)) -> Tile {
+        // row `y` of the tile (which may be just above or below it) split
+        // into its cells' west neighbors, the cells and their east neighbors
+        let line = |y: i64| {
+            let (dy, y) = (y.div_euclid(TILE), y.rem_euclid(TILE) as usize);
+            let [w, c, e] = [-1, 0, 1].map(|dx| {
+                self.tiles
+                    .get(&(tx + dx, ty + dy))
+                    .map_or(0, |tile| tile[y])
+            });
+            [(c << 1) | (w >> 63), c, (c >> 1) | (e << 63)]
+        };
+        let mut out = EMPTY;
+        for (y, word) in out.iter_mut().enumerate() {
+            let y = y as i64;
+            *word = self.rule.next(&[line(y - 1), line(y), line(y + 1)]);
+        }
+        out
+    }
+}
+
+impl Hash for Plane {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        let mut tiles: Vec<_> = self.tiles.iter().collect();
+        tiles.sort_unstable_by_key(|(key, _)| **key);
+        tiles.hash(state);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn glider(plane: &mut Plane, at: Point) {
+        for (x, y) in [(1, 0), (2, 1), (0, 2), (1, 2), (2, 2)] {
+            plane.set(at.clone() + Point { x, y }, true);
+        }
+    }
+
+    #[test]
+    fn test_matches_packed() -> Result<()> {
+        for rule in ["B3/S23", "B36/S23", "B2/S34H", "B2/SV"] {
+            let rule: LifeRule = rule.parse()?;
+            let mut board = Board::empty(200, 150);
+            for i in 0..900i64 {
+                board.set(
+                    Point {
+                        x: 40 + (i * 37) % 120,
+                        y: 40 + (i * 11 + i / 7) % 70,
+                    },
+                    true,
+                );
+            }
+            let mut plane = Plane::from_board(&board, &rule)?;
+            let word_rule = WordRule::from_rule(&rule).unwrap();
+            for _ in 0..12 {
+                plane.step();
+                board = crate::packed::step(&board, &word_rule);
+            }
+            // the board wraps, so only compare while the pattern is away
+            // from its edges
+            let window = plane.window(&Point { x: 0, y: 0 }, 200, 150);
+            assert_eq!(window.states(), board.states(), "{}", rule);
+            assert_eq!(plane.population(), board.alive() as u64);
+        }
+        Ok(())
+    }
+    #[test]
+    fn test_glider_leaves_origin() -> Result<()> {
+        let mut plane = Plane::new(&LifeRule::life())?;
+        glider(&mut plane, Point { x: 0, y: 0 });
+        // run it backwards by flipping it, so it heads north west into
+        // negative coordinates and across several tile edges
+        let mut flipped = Plane::new(&LifeRule::life())?;
+        for (x, y) in [(1, 0), (2, 1), (0, 2), (1, 2), (2, 2)] {
+            flipped.set(Point { x: -x, y: -y }, true);
+        }
+        for _ in 0..400 {
+            plane.step();
+            flipped.step();
+        }
+        assert_eq!(plane.population(), 5);
+        assert_eq!(flipped.population(), 5);
+        let ahead = plane.window(&Point { x: 100, y: 100 }, 4, 4);
+        assert_eq!(ahead.alive(), 5);
+        let behind = flipped.window(&Point { x: -103, y: -103 }, 4, 4);
+        assert_eq!(behind.alive(), 5);
+        assert!(plane.tiles.len() <= 4);
+        Ok(())
+    }
+    #[test]
+    fn test_changes() -> Result<()> {
+        let mut plane = Plane::new(&LifeRule::life())?;
+        for x in 62..65 {
+            plane.set(Point { x, y: 0 }, true);
+        }
+        let before = plane.clone();
+        plane.step();
+        // a blinker across a tile edge turns two cells on and two off
+        assert_eq!(before.changes(&plane), (2, 2));
+        Ok(())
+    }
+    #[test]
+    fn test_rejects_unsupported_rules() {
+        for rule in ["B2/S/C3", "B2-a/S12", "B03/S23"] {
+            assert!(Plane::new(&rule.parse().unwrap()).is_err(), "{}", rule);
+        }
+    }
+}
@@ -0,0 +1,162 @@
+use std::{io::Write, path::Path, time::Duration};
+
+use crate::gol::Point;
+
+/// Layout of the stats file
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Format {
+    /// Header line followed by one comma separated row per generation
+    Csv,
+    /// One JSON object per line
+    Ndjson,
+}
+
+impl Format {
+    /// Format named by the extension of `path`, CSV unless it's `.ndjson`
+    /// or `.jsonl`
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some(e) if e.eq_ignore_ascii_case("ndjson") || e.eq_ignore_ascii_case("jsonl") => {
+                Format::Ndjson
+            }
+            _ => Format::Csv,
+        }
+    }
+}
+
+/// What happened in one generation
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Sample {
+    pub generation: u64,
+    pub population: u64,
+    /// Cells born and cells which died since the previous sample, `None`
+    /// when they aren't known (e.g. hashlife skipping generations)
+    pub changes: Option<(u64, u64)>,
+    /// Top left and bottom right live cells, `None` on an empty board
+    pub bounds: Option<(Point, Point)>,
+    /// Time taken computing this generation
+    pub step_time: Duration,
+}
+
+const COLUMNS: &str = "generation,population,births,deaths,min_x,min_y,max_x,max_y,step_us";
+
+/// Writes samples one line at a time as they come in
+pub struct Writer<W: Write> {
+    out: W,
+    format: Format,
+    started: bool,
+}
+
+impl<W: Write> Writer<W> {
+    pub fn new(out: W, format: Format) -> Self {
+        Self {
+            out,
+            format,
+            started: false,
+        }
+    }
+    pub fn write(&mut self, sample: &Sample) -> std::io::Result<()> {
+        let (births, deaths) = sample.changes.unzip();
+        let step_us = sample.step_time.as_micros();
+        match self.format {
+            Format::Csv => {
+                if !self.started {
+                    writeln!(self.out, "{}", COLUMNS)?;
+                }
+                let (min, max) = sample.bounds.clone().unzip();
+                writeln!(
+                    self.out,
+                    "{},{},{},{},{},{},{},{},{}",
+                    sample.generation,
+                    sample.population,
+                    field(births),
+                    field(deaths),
+                    field(min.as_ref().map(|p| p.x)),
+                    field(min.as_ref().map(|p| p.y)),
+                    field(max.as_ref().map(|p| p.x)),
+                    field(max.as_ref().map(|p| p.y)),
+                    step_us
+                )?;
+            }
+            Format::Ndjson => {
+                let bounds = match &sample.bounds {
+                    Some((min, max)) => format!("[{},{},{},{}]", min.x, min.y, max.x, max.y),
+                    None => "null".to_string(),
+                };
+                writeln!(
+                    self.out,
+                    r#"{{"generation":{},"population":{},"births":{},"deaths":{},"bounds":{},"step_us":{}}}"#,
+                    sample.generation,
+                    sample.population,
+                    births.map_or("null".to_string(), |v| v.to_string()),
+                    deaths.map_or("null".to_string(), |v| v.to_string()),
+                    bounds,
+                    step_us
+                )?;
+            }
+        }
+        self.started = true;
+        Ok(())
+    }
+    pub fn flush(&mut self) -> std::io::Result<()> {
+        self.out.flush()
+    }
+}
+
+/// CSV field, empty when missing
+fn field<T: ToString>(v: Option<T>) -> String {
+    v.map_or(String::new(), |v| v.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn samples() -> [Sample; 2] {
+        [
+            Sample {
+                generation: 0,
+                population: 0,
+                changes: None,
+                bounds: None,
+                step_time: Duration::ZERO,
+            },
+            Sample {
+                generation: 1,
+                population: 3,
+                changes: Some((3, 0)),
+                bounds: Some((Point { x: -1, y: 2 }, Point { x: 1, y: 2 })),
+                step_time: Duration::from_micros(150),
+            },
+        ]
+    }
+    fn written(format: Format) -> String {
+        let mut writer = Writer::new(Vec::new(), format);
+        for s in samples() {
+            writer.write(&s).unwrap();
+        }
+        String::from_utf8(writer.out).unwrap()
+    }
+
+    #[test]
+    fn test_csv() {
+        assert_eq!(
+            written(Format::Csv),
+            format!("{}\n0,0,,,,,,,0\n1,3,3,0,-1,2,1,2,150\n", COLUMNS)
+        );
+    }
+    #[test]
+    fn test_ndjson() {
+        let out = written(Format::Ndjson);
+        let lines: Vec<_> = out.lines().collect();
+        assert_eq!(
+            lines,
+            [
+                r#"{"generation":0,"population":0,"births":null,"deaths":null,"bounds":null,"step_us":0}"#,
+                r#"{"generation":1,"population":3,"births":3,"deaths":0,"bounds":[-1,2,1,2],"step_us":150}"#,
+            ]
+        );
+        assert_eq!(Format::from_path(Path::new("a.NDJSON")), Format::Ndjson);
+        assert_eq!(Format::from_path(Path::new("a.txt")), Format::Csv);
+    }
+}
@@ -0,0 +1,85 @@
+/// Bars of a sparkline from lowest to highest
+const BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// Generations kept in the history laid out across a row of the screen,
+/// each column showing the highest population of the generations it covers
+pub struct Timeline {
+    first: u64,
+    latest: u64,
+    /// Highest population in each column, `None` where no generation falls
+    columns: Vec<Option<u64>>,
+}
+
+impl Timeline {
+    /// Timeline `width` columns wide over `samples` of generation and
+    /// population in increasing order of generation
+    pub fn new(samples: &[(u64, u64)], width: usize) -> Option<Self> {
+        let (first, latest) = (samples.first()?.0, samples.last()?.0);
+        if width == 0 {
+            return None;
+        }
+        let mut timeline = Self {
+            first,
+            latest,
+            columns: vec![None; width],
+        };
+        for (g, p) in samples {
+            let c = timeline.column(*g);
+            timeline.columns[c] = Some(timeline.columns[c].map_or(*p, |m| m.max(*p)));
+        }
+        Some(timeline)
+    }
+    /// Column which `generation` falls in
+    pub fn column(&self, generation: u64) -> usize {
+        let span = (self.latest - self.first + 1) as u128;
+        let g = generation.clamp(self.first, self.latest) - self.first;
+        (g as u128 * self.columns.len() as u128 / span) as usize
+    }
+    /// Earliest generation falling in `column`
+    pub fn generation(&self, column: usize) -> u64 {
+        let span = (self.latest - self.first + 1) as u128;
+        let c = column.min(self.columns.len() - 1) as u128;
+        self.first + (c * span).div_ceil(self.columns.len() as u128) as u64
+    }
+    /// Generations covered by one column, at least one
+    pub fn stride(&self) -> u64 {
+        ((self.latest - self.first + 1) / self.columns.len() as u64).max(1)
+    }
+    /// Populations as bars scaled to the highest one, blank where there are
+    /// no generations
+    pub fn sparkline(&self) -> String {
+        let max = self.columns.iter().flatten().max().copied().unwrap_or(0);
+        self.columns
+            .iter()
+            .map(|c| match c {
+                None => ' ',
+                Some(_) if max == 0 => BARS[0],
+                Some(p) => BARS[(p * (BARS.len() as u64 - 1)).div_ceil(max) as usize],
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sparkline() {
+        let samples: Vec<_> = (0..8).map(|g| (g, g * 10)).collect();
+        let t = Timeline::new(&samples, 8).unwrap();
+        assert_eq!(t.sparkline(), "▁▂▃▄▅▆▇█");
+        // two generations to a column keeps the higher population
+        let t = Timeline::new(&samples, 4).unwrap();
+        assert_eq!(t.sparkline(), "▂▄▆█");
+        assert_eq!((t.column(5), t.stride()), (2, 2));
+        assert_eq!((t.generation(2), t.generation(9)), (4, 6));
+    }
+    #[test]
+    fn test_gaps() {
+        let t = Timeline::new(&[(0, 1), (30, 1)], 4).unwrap();
+        assert_eq!(t.sparkline(), "█  █");
+        assert_eq!(t.column(100), 3);
+        assert!(Timeline::new(&[], 4).is_none());
+    }
+}
@@ -0,0 +1,284 @@
+use anyhow::{anyhow, bail, ensure, Error, Result};
+use std::{fmt::Display, str::FromStr};
+
+use crate::gol::{Board, Point};
+
+/// How the edges of a bounded board are joined together
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Topology {
+    /// Edges are not joined, cells past them are always dead
+    Plane,
+    /// Opposite edges are joined
+    #[default]
+    Torus,
+    /// Opposite edges are joined, the top and bottom edges (`horizontal`)
+    /// or the left and right edges with a twist
+    Klein { horizontal: bool },
+    /// Both pairs of opposite edges are joined with a twist
+    CrossSurface,
+    /// The top edge is joined to the left edge and the bottom edge to the
+    /// right one, only square boards can be spheres
+    Sphere,
+}
+
+impl Topology {
+    /// Cell of a `w` by `h` board which `pt` refers to, or `None` for cells
+    /// past an edge of a plane or a corner of a sphere
+    pub fn wrap(self, pt: &Point, w: u32, h: u32) -> Option<Point> {
+        let (w, h) = (w as i64, h as i64);
+        let Point { mut x, mut y } = pt.clone();
+        // going across a twisted edge mirrors the other coordinate
+        let (flip_x, flip_y) = match self {
+            Topology::Plane => {
+                return ((0..w).contains(&x) && (0..h).contains(&y)).then_some(Point { x, y });
+            }
+            Topology::Torus => (false, false),
+            Topology::Klein { horizontal } => (horizontal, !horizontal),
+            Topology::CrossSurface => (true, true),
+            Topology::Sphere => {
+                match ((0..w).contains(&x), (0..h).contains(&y)) {
+                    (true, true) => (),
+                    (false, false) => return None,
+                    (true, false) if y < 0 => (x, y) = (-y - 1, x),
+                    (true, false) => (x, y) = (w - 1 - (y - h), x),
+                    (false, true) if x < 0 => (x, y) = (y, -x - 1),
+                    (false, true) => (x, y) = (y, h - 1 - (x - w)),
+                }
+                return ((0..w).contains(&x) && (0..h).contains(&y)).then_some(Point { x, y });
+            }
+        };
+        if flip_x && y.div_euclid(h) % 2 != 0 {
+            x = w - 1 - x;
+        }
+        if flip_y && x.div_euclid(w) % 2 != 0 {
+            y = h - 1 - y;
+        }
+        Some(Point {
+            x: x.rem_euclid(w),
+            y: y.rem_euclid(h),
+        })
+    }
+}
+
+/// Bounded grid from the `--topology` option, either just a topology or a
+/// Golly-style suffix such as `:T100,80` or `:K100*,80` giving its size too
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Grid {
+    pub topology: Topology,
+    /// Width and height of the board, `None` keeps the size of the input
+    pub size: Option<(u32, u32)>,
+}
+
+impl Grid {
+    /// Check that `width` by `height` board fits the topology
+    pub fn check(&self, width: u32, height: u32) -> Result<()> {
+        ensure!(
+            self.topology != Topology::Sphere || width == height,
+            "a sphere needs a square board, got {}x{}",
+            width,
+            height
+        );
+        Ok(())
+    }
+    /// `board` with the grid's topology, centered on a board of the grid's
+    /// size if it has one, which has to be large enough to hold it
+    pub fn apply(&self, board: Board) -> Result<Board> {
+        let board = match self.size {
+            Some((w, h)) if (w, h) != (board.width(), board.height()) => {
+                ensure!(
+                    w >= board.width() && h >= board.height(),
+                    "the pattern is {}x{}, larger than the {}x{} grid",
+                    board.width(),
+                    board.height(),
+                    w,
+                    h
+                );
+                board.resized(w, h)
+            }
+            _ => board,
+        };
+        self.check(board.width(), board.height())?;
+        Ok(board.with_topology(self.topology))
+    }
+}
+
+fn parse_dimension(s: &str) -> Result<(u32, bool)> {
+    let (n, twisted) = match s.strip_suffix('*') {
+        Some(n) => (n, true),
+        None => (s, false),
+    };
+    ensure!(
+        !n.contains(['+', '-']),
+        "shifted edges are not supported ('{}')",
+        s
+    );
+    let n: u32 = n
+        .parse()
+        .map_err(|_| anyhow!("invalid grid dimension '{}'", s))?;
+    ensure!(n > 0, "unbounded grid dimensions are not supported");
+    Ok((n, twisted))
+}
+
+impl FromStr for Grid {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let topology = match s.to_ascii_lowercase().as_str() {
+            "plane" => Some(Topology::Plane),
+            "torus" => Some(Topology::Torus),
+            "klein" => Some(Topology::Klein { horizontal: true }),
+            "cross" | "cross-surface" => Some(Topology::CrossSurface),
+            "sphere" => Some(Topology::Sphere),
+            _ => None,
+        };
+        if let Some(topology) = topology {
+            return Ok(Self {
+                topology,
+                size: None,
+            });
+        }
+
+        let spec = s.strip_prefix(':').unwrap_or(s);
+        let mut chars = spec.chars();
+        let kind = chars
+            .next()
+            .ok_or_else(|| anyhow!("empty topology"))?
+            .to_ascii_uppercase();
+        let dims = chars
+            .as_str()
+            .split(',')
+            .map(parse_dimension)
+            .collect::<Result<Vec<_>>>()?;
+        let ((w, tw), (h, th)) = match dims[..] {
+            [d] if kind == 'S' => (d, d),
+            [w, h] => (w, h),
+            _ => bail!("expected a width and height in '{}'", s),
+        };
+        let topology = match (kind, tw, th) {
+            ('P', false, false) => Topology::Plane,
+            ('T', false, false) => Topology::Torus,
+            ('K', true, false) => Topology::Klein { horizontal: true },
+            ('K', false, true) => Topology::Klein { horizontal: false },
+            ('K', ..) => bail!(
+                "a Klein bottle needs exactly one twisted dimension in '{}'",
+                s
+            ),
+            ('C', false, false) => Topology::CrossSurface,
+            ('S', false, false) => Topology::Sphere,
+            ('P' | 'T' | 'C' | 'S', ..) => bail!("only Klein bottles have twisted edges"),
+            _ => bail!("unknown topology '{}'", s),
+        };
+        let grid = Self {
+            topology,
+            size: Some((w, h)),
+        };
+        grid.check(w, h)?;
+        Ok(grid)
+    }
+}
+
+impl Display for Grid {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Some((w, h)) = self.size else {
+            return write!(
+                f,
+                "{}",
+                match self.topology {
+                    Topology::Plane => "plane",
+                    Topology::Torus => "torus",
+                    Topology::Klein { .. } => "klein",
+                    Topology::CrossSurface => "cross",
+                    Topology::Sphere => "sphere",
+                }
+            );
+        };
+        match self.topology {
+            Topology::Plane => write!(f, ":P{},{}", w, h),
+            Topology::Torus => write!(f, ":T{},{}", w, h),
+            Topology::Klein { horizontal: true } => write!(f, ":K{}*,{}", w, h),
+            Topology::Klein { horizontal: false } => write!(f, ":K{},{}*", w, h),
+            Topology::CrossSurface => write!(f, ":C{},{}", w, h),
+            Topology::Sphere => write!(f, ":S{}", w),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wrap(t: Topology, x: i64, y: i64) -> Option<(i64, i64)> {
+        t.wrap(&Point { x, y }, 10, 8).map(|p| (p.x, p.y))
+    }
+
+    #[test]
+    fn test_parse_golly() -> Result<()> {
+        for s in [
+            ":T100,80",
+            ":P10,10",
+            ":K100*,80",
+            ":K100,80*",
+            ":C5,7",
+            ":S50",
+        ] {
+            assert_eq!(s.parse::<Grid>()?.to_string(), s);
+        }
+        let klein: Grid = "K100*,80".parse()?;
+        assert_eq!(klein.topology, Topology::Klein { horizontal: true });
+        assert_eq!(klein.size, Some((100, 80)));
+        assert_eq!("sphere".parse::<Grid>()?.size, None);
+        for s in [
+            ":T100",
+            ":K100,80",
+            ":T100*,80",
+            ":S10,20",
+            ":T0,10",
+            ":T10+2,10",
+            ":X1,1",
+        ] {
+            assert!(s.parse::<Grid>().is_err(), "accepted {}", s);
+        }
+        Ok(())
+    }
+    #[test]
+    fn test_wrap() {
+        assert_eq!(wrap(Topology::Torus, -1, 8), Some((9, 0)));
+        assert_eq!(wrap(Topology::Plane, -1, 3), None);
+        assert_eq!(wrap(Topology::Plane, 4, 3), Some((4, 3)));
+        // the twisted edges mirror the coordinate along them
+        let klein = Topology::Klein { horizontal: true };
+        assert_eq!(wrap(klein, 2, -1), Some((7, 7)));
+        assert_eq!(wrap(klein, -1, 2), Some((9, 2)));
+        assert_eq!(wrap(Topology::CrossSurface, -1, 2), Some((9, 5)));
+        assert_eq!(wrap(Topology::CrossSurface, 2, 8), Some((7, 0)));
+    }
+    #[test]
+    fn test_apply_centers() -> Result<()> {
+        let mut board = Board::empty(2, 2);
+        board.set(Point { x: 0, y: 0 }, true);
+        let grid: Grid = ":P6,4".parse()?;
+        let sized = grid.apply(board.clone())?;
+        assert_eq!((sized.width(), sized.height()), (6, 4));
+        assert!(sized[Point { x: 2, y: 1 }]);
+        assert_eq!(sized.topology(), Topology::Plane);
+        assert!("sphere".parse::<Grid>()?.apply(Board::empty(3, 2)).is_err());
+        // patterns aren't cropped to fit
+        assert!(grid.apply(Board::empty(7, 2)).is_err());
+        Ok(())
+    }
+    #[test]
+    fn test_sphere_edges() {
+        let wrap = |x, y| {
+            Topology::Sphere
+                .wrap(&Point { x, y }, 8, 8)
+                .map(|p| (p.x, p.y))
+        };
+        // above the top row is the left column and past the right column is
+        // the bottom row
+        assert_eq!(wrap(3, -1), Some((0, 3)));
+        assert_eq!(wrap(-1, 3), Some((3, 0)));
+        assert_eq!(wrap(8, 3), Some((3, 7)));
+        assert_eq!(wrap(3, 8), Some((7, 3)));
+        assert_eq!(wrap(-1, -1), None);
+    }
+}
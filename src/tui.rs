@@ -0,0 +1,501 @@
+use std::fs::File;
+use std::io::BufWriter;
+use std::sync;
+use std::sync::atomic::{AtomicBool, AtomicU8};
+use std::sync::mpsc::Receiver;
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, Context, Result};
+use backend::{Input, Terminal};
+use gol_rs::bgrid::{Charset, Color, Frame, Shade};
+use gol_rs::census::Census;
+use gol_rs::cycle::{Cycle, Detector, OnCycle};
+use gol_rs::hashlife::MAX_STEP;
+use gol_rs::history::History;
+use gol_rs::neighborhood::Neighborhood;
+use gol_rs::rule::AnyRule;
+use gol_rs::timeline::Timeline;
+use gol_rs::{stats, Mask, Point, Simulation, World};
+use scopeguard::defer;
+
+use crate::{
+    args::{Args, Backend},
+    mk_pool,
+};
+
+mod backend;
+
+/// Census report of `world` as lines for the overlay
+fn census_lines(world: &World, turn: u64, rule: &AnyRule) -> Vec<String> {
+    let census = match rule {
+        AnyRule::Life(r) => Census::take(&world.board(), r),
+        AnyRule::Ltl(_) | AnyRule::Wireworld(_) => Err(anyhow!("the census needs a B/S rule")),
+    };
+    match census {
+        Ok(census) => std::iter::once(format!("census at turn {}", turn))
+            .chain(census.lines())
+            .collect(),
+        Err(e) => vec![e.to_string()],
+    }
+}
+
+/// Draw `lines` in the top right corner of the screen
+fn draw_overlay(term: &mut dyn Terminal, lines: &[String]) {
+    let width = lines.iter().map(|l| l.chars().count()).max().unwrap_or(0) as i32;
+    let (cols, rows) = term.size();
+    let x = (cols - width - 1).max(0);
+    for (y, line) in lines.iter().enumerate().take(rows as usize) {
+        term.put(x, y as i32, line, Color::Green);
+    }
+}
+
+enum Event {
+    /// World after a step and the generation it is at
+    TurnEnd(World, u64),
+    /// The world has become periodic
+    Cycle(Cycle),
+    Input(Input),
+}
+
+/// Draw `world` with the viewport at `offset` along with the status lines
+/// about it, wrapping the offset around bounded boards
+fn draw_world(
+    term: &mut dyn Terminal,
+    world: &World,
+    turn: u64,
+    offset: &mut Point,
+    bg: char,
+    chset: Charset,
+    rule: &AnyRule,
+) -> Result<()> {
+    let hex = rule.neighborhood() == Neighborhood::Hexagonal;
+    let (cols, rows) = term.size();
+    let (b, viewport, alive) = match world {
+        World::Bounded(b) => {
+            offset.remap(b.width() * if hex { 2 } else { 1 }, b.height());
+            let viewport = Mask {
+                x: (offset.x) as u32,
+                y: (offset.y) as u32,
+                w: cols as u32,
+                h: rows as u32,
+            };
+            let alive = b.alive() as u64;
+            (b.clone(), viewport, alive)
+        }
+        World::Plane(p) => {
+            let (sx, sy) = chset.scale();
+            let (w, h) = (cols as u32 * sx, rows as u32 * sy);
+            // skewed rows reach up to half the screen height
+            // further right
+            let (corner, w) = if hex {
+                let corner = Point {
+                    x: offset.x.div_euclid(2),
+                    y: offset.y,
+                };
+                (corner, w + h)
+            } else {
+                (offset.clone(), w)
+            };
+            let viewport = Mask {
+                x: 0,
+                y: 0,
+                w: cols as u32,
+                h: rows as u32,
+            };
+            (p.window(&corner, w, h), viewport, p.population())
+        }
+    };
+    let frame = Frame::new(b.clone(), viewport)
+        .hex_skewed(hex)
+        .with_palette(rule.palette());
+    let screen_view = Mask {
+        x: 0,
+        y: 0,
+        w: cols as u32,
+        h: rows as u32,
+    };
+    term.clear();
+    for (pt, c, shade) in frame.render(bg, chset) {
+        if !screen_view.contains(&pt) {
+            return Err(anyhow!(
+                "tried to draw outside the viewport: {} not in {}",
+                pt,
+                screen_view
+            ));
+        }
+        let color = match shade {
+            Shade::Cell(c) => c,
+            Shade::Border => Color::White,
+        };
+        term.put(pt.x as i32, pt.y as i32, &String::from(c), color);
+    }
+    let status = |term: &mut dyn Terminal, y, text: String| term.put(0, y, &text, Color::Green);
+    status(term, 0, format!("turn   {}", turn));
+    status(term, 1, format!("alive  {}", alive));
+    status(term, 2, format!("offset {}", offset));
+    if b.dying() > 0 {
+        status(term, 3, format!("dying  {}", b.dying()));
+    }
+    Ok(())
+}
+
+/// Recorded generation at or before `target` to look at, `None` to go back
+/// to the live world once `target` reaches the latest generation
+fn seek(history: &History, target: u64, latest: Option<u64>) -> Option<(World, u64)> {
+    if latest.is_some_and(|l| target >= l) {
+        return None;
+    }
+    let t = history
+        .at_or_before(target)
+        .or_else(|| history.range().map(|(first, _)| first))?;
+    Some((World::Bounded(history.get(t)?), t))
+}
+
+/// Timeline of the generations kept fitted to a row `width` wide, between
+/// labels with the first and latest of them
+fn timeline_layout(history: &History, width: usize) -> Option<(String, Timeline, String)> {
+    let (first, latest) = history.range()?;
+    let (first_label, latest_label) = (format!("{} ", first), format!(" {}", latest));
+    let bar = width.saturating_sub(first_label.len() + latest_label.len());
+    let timeline = Timeline::new(&history.populations(), bar)?;
+    Some((first_label, timeline, latest_label))
+}
+
+/// Draw the timeline of the generations kept along the bottom row, with the
+/// one being shown highlighted
+fn draw_timeline(term: &mut dyn Terminal, history: &History, shown: u64) {
+    let (cols, rows) = term.size();
+    let Some((first_label, timeline, latest_label)) = timeline_layout(history, cols as usize)
+    else {
+        return;
+    };
+    let y = rows - 1;
+    let spark = timeline.sparkline();
+    let x = first_label.len() as i32;
+    term.put(0, y, &first_label, Color::Green);
+    term.put(x, y, &spark, Color::Green);
+    term.put(
+        x + spark.chars().count() as i32,
+        y,
+        &latest_label,
+        Color::Green,
+    );
+    let column = timeline.column(shown);
+    if let Some(c) = spark.chars().nth(column) {
+        term.put(x + column as i32, y, &String::from(c), Color::Red);
+    }
+}
+
+/// Generation under column `x` of the timeline, if it's over the bars
+fn timeline_at(term: &dyn Terminal, history: &History, x: i32) -> Option<u64> {
+    let (first_label, timeline, _) = timeline_layout(history, term.size().0 as usize)?;
+    let column = usize::try_from(x - first_label.len() as i32).ok()?;
+    (column < timeline.sparkline().chars().count()).then(|| timeline.generation(column))
+}
+
+/// Generations `[` and `]` move by, one column of the timeline
+fn scrub_stride(term: &dyn Terminal, history: &History) -> u64 {
+    Timeline::new(&history.populations(), term.size().0 as usize).map_or(1, |t| t.stride())
+}
+
+#[allow(clippy::too_many_arguments)]
+fn run_event_loop(
+    running: &AtomicBool,
+    paused: &AtomicBool,
+    tx: Receiver<Event>,
+    backend: Backend,
+    chset: Charset,
+    bg: char,
+    step: Option<&AtomicU8>,
+    rule: &AnyRule,
+    undo_bytes: usize,
+) -> Result<()> {
+    let mut term = backend::open(backend)?;
+    let term = term.as_mut();
+    defer! { running.store(false, sync::atomic::Ordering::SeqCst); }
+
+    let (cols, rows) = term.size();
+    let scroll_inc: i64 = (cols.max(rows) / 20 / chset.scale().1 as i32).into();
+    let mut offset = Point { x: 0, y: 0 };
+    let mut cycle = None;
+    let mut last: Option<(World, u64)> = None;
+    let mut history = History::new(undo_bytes);
+    // past generation being looked at, the simulation is paused meanwhile
+    let mut browsing: Option<(World, u64)> = None;
+    let mut overlay: Option<Vec<String>> = None;
+    // text typed after ':', and the outcome of the last command
+    let mut command: Option<String> = None;
+    let mut message: Option<String> = None;
+    while running.load(sync::atomic::Ordering::SeqCst) {
+        let mut ev = Option::None;
+        while ev.is_none() {
+            ev = term
+                .poll()?
+                .map(Event::Input)
+                .or_else(|| tx.try_recv().ok());
+            if ev.is_none() {
+                sleep(Duration::from_millis(1));
+            }
+        }
+        let ev = ev.unwrap();
+        let mut redraw = true;
+        let current = browsing.as_ref().or(last.as_ref()).map(|(_, t)| *t);
+        let latest = last.as_ref().map(|(_, t)| *t);
+        if let Event::Input(_) = ev {
+            message = None;
+        }
+        match ev {
+            Event::Input(Input::Quit) => {
+                running.store(false, sync::atomic::Ordering::SeqCst);
+                redraw = false;
+            }
+            Event::Input(input) if command.is_some() => {
+                let text = command.as_mut().unwrap();
+                match input {
+                    Input::Enter => {
+                        let target = text
+                            .strip_prefix("goto")
+                            .or_else(|| text.strip_prefix('g'))
+                            .and_then(|n| n.trim().parse::<u64>().ok());
+                        match target {
+                            Some(n) => {
+                                browsing = seek(&history, n, latest);
+                                paused.store(browsing.is_some(), sync::atomic::Ordering::SeqCst);
+                            }
+                            None => message = Some(format!("unknown command ':{}'", text)),
+                        }
+                        command = None;
+                    }
+                    Input::Escape => command = None,
+                    Input::Backspace => {
+                        text.pop();
+                    }
+                    Input::Char(c) => text.push(c),
+                    _ => (),
+                }
+            }
+            Event::Input(Input::Char(':')) => command = Some(String::new()),
+            Event::Input(Input::Char('[')) => {
+                if let Some(t) = current {
+                    let stride = scrub_stride(term, &history);
+                    browsing = seek(&history, t.saturating_sub(stride), latest);
+                    paused.store(browsing.is_some(), sync::atomic::Ordering::SeqCst);
+                }
+            }
+            Event::Input(Input::Char(']')) => {
+                if let Some((_, t)) = &browsing {
+                    let target = history
+                        .at_or_before(t + scrub_stride(term, &history))
+                        .filter(|g| g > t)
+                        .or_else(|| history.after(*t));
+                    browsing = target.and_then(|g| seek(&history, g, latest));
+                    paused.store(browsing.is_some(), sync::atomic::Ordering::SeqCst);
+                }
+            }
+            Event::TurnEnd(world, turn) => {
+                if let World::Bounded(b) = &world {
+                    history.record(turn, b);
+                }
+                last = Some((world, turn));
+                redraw = browsing.is_none();
+            }
+            Event::Cycle(c) => cycle = Some(c),
+            Event::Input(Input::Left | Input::Char('h')) => offset.x -= scroll_inc,
+            Event::Input(Input::Right | Input::Char('l')) => offset.x += scroll_inc,
+            Event::Input(Input::Up | Input::Char('k') | Input::Scroll { down: false }) => {
+                offset.y -= scroll_inc
+            }
+            Event::Input(Input::Down | Input::Char('j') | Input::Scroll { down: true }) => {
+                offset.y += scroll_inc
+            }
+            Event::Input(Input::Click { x, y })
+                if y == term.size().1 - 1 && command.is_none() && message.is_none() =>
+            {
+                if let Some(g) = timeline_at(term, &history, x) {
+                    browsing = seek(&history, g, latest);
+                    paused.store(browsing.is_some(), sync::atomic::Ordering::SeqCst);
+                }
+            }
+            Event::Input(Input::Resize) => (),
+            Event::Input(Input::Char('+' | '=')) => {
+                if let Some(step) = step {
+                    let k = step.load(sync::atomic::Ordering::SeqCst);
+                    step.store((k + 1).min(MAX_STEP), sync::atomic::Ordering::SeqCst);
+                }
+            }
+            Event::Input(Input::Char('-')) => {
+                if let Some(step) = step {
+                    let k = step.load(sync::atomic::Ordering::SeqCst);
+                    step.store(k.saturating_sub(1), sync::atomic::Ordering::SeqCst);
+                }
+            }
+            Event::Input(Input::Char(',' | '<')) => {
+                let past = current
+                    .and_then(|t| history.before(t))
+                    .and_then(|t| Some((World::Bounded(history.get(t)?), t)));
+                if past.is_some() {
+                    paused.store(true, sync::atomic::Ordering::SeqCst);
+                    browsing = past;
+                }
+            }
+            Event::Input(Input::Char('.' | '>')) => {
+                if let Some((_, t)) = &browsing {
+                    browsing = history
+                        .after(*t)
+                        .filter(|t| Some(*t) != latest)
+                        .and_then(|t| Some((World::Bounded(history.get(t)?), t)));
+                    if browsing.is_none() {
+                        paused.store(false, sync::atomic::Ordering::SeqCst);
+                    }
+                }
+            }
+            Event::Input(Input::Char(' ')) if browsing.is_none() => {
+                paused.fetch_xor(true, sync::atomic::Ordering::SeqCst);
+                redraw = false;
+            }
+            Event::Input(Input::Char('c')) => {
+                overlay = match (&overlay, browsing.as_ref().or(last.as_ref())) {
+                    (None, Some((world, turn))) => Some(census_lines(world, *turn, rule)),
+                    _ => None,
+                };
+            }
+            Event::Input(Input::Char('q')) => {
+                running.store(false, sync::atomic::Ordering::SeqCst);
+                redraw = false;
+            }
+            _ => redraw = false,
+        }
+        let Some((world, turn)) = browsing.as_ref().or(last.as_ref()).filter(|_| redraw) else {
+            continue;
+        };
+        draw_world(term, world, *turn, &mut offset, bg, chset, rule)?;
+        if let Some(step) = step {
+            let k = step.load(sync::atomic::Ordering::SeqCst);
+            term.put(0, 4, &format!("step   2^{}", k), Color::Green);
+        }
+        if let Some(cycle) = cycle {
+            term.put(0, 5, &format!("cycle  {}", cycle), Color::Green);
+        }
+        if let Some((first, latest)) = history.range() {
+            let status = match &browsing {
+                Some((_, t)) => format!("undo   at {} of {}..{}", t, first, latest),
+                None => format!("undo   {}..{}", first, latest),
+            };
+            term.put(0, 6, &status, Color::Green);
+        }
+        if let Some(lines) = &overlay {
+            draw_overlay(term, lines);
+        }
+        let bottom = term.size().1 - 1;
+        match (&command, &message) {
+            (Some(text), _) => term.put(0, bottom, &format!(":{}", text), Color::Green),
+            (None, Some(m)) => term.put(0, bottom, m, Color::Red),
+            (None, None) => draw_timeline(term, &history, *turn),
+        }
+        term.refresh()?;
+    }
+    Ok(())
+}
+/// Run `simulation` on its own thread while the terminal interface shows
+/// it, until the user quits. Returns the cycle found, if any, and the final
+/// cells
+pub fn run(
+    mut simulation: Simulation,
+    args: &Args,
+    threads: u16,
+    mut stats: Option<stats::Writer<BufWriter<File>>>,
+) -> Result<(Option<Cycle>, Simulation)> {
+    let (sx, tx) = std::sync::mpsc::channel();
+    let bsx = sx.clone();
+    let running = AtomicBool::new(true);
+    let rule = &simulation.rule().clone();
+    let on_cycle = args.on_cycle;
+    let step = AtomicU8::new(args.step.min(MAX_STEP));
+    let step = &step;
+    let running = &running;
+    let paused = AtomicBool::new(false);
+    let paused = &paused;
+    let undo_bytes = args.undo_memory << 20;
+    std::thread::scope(move |s| {
+        let hashlife = simulation.is_hashlife();
+        let sim = s.spawn(move || {
+            mk_pool(threads as usize)
+                .expect("failed to create threadpool")
+                .install(move || {
+                    let mut detector = Detector::new(args.history);
+                    let mut cycle = None;
+                    let mut prev = simulation.world();
+                    prev.observe(&mut detector, 0);
+                    let mut written = Ok(());
+                    if let Some(stats) = stats.as_mut() {
+                        written = stats.write(&prev.sample(None, 0, Duration::ZERO));
+                    }
+                    while running.load(sync::atomic::Ordering::SeqCst) {
+                        if paused.load(sync::atomic::Ordering::SeqCst) {
+                            sleep(Duration::from_millis(1));
+                            continue;
+                        }
+                        let started = Instant::now();
+                        simulation.set_step(step.load(sync::atomic::Ordering::SeqCst));
+                        let gen = simulation.step();
+                        let world = simulation.world();
+                        if let (Some(s), true) = (stats.as_mut(), written.is_ok()) {
+                            // hashlife may skip generations, so births and
+                            // deaths can't be told apart from the last frame
+                            let prev = (!hashlife).then_some(&prev);
+                            written = s.write(&world.sample(prev, gen, started.elapsed()));
+                        }
+                        if stats.is_some() {
+                            prev = world.clone();
+                        }
+                        let found = if cycle.is_none() {
+                            world.observe(&mut detector, gen)
+                        } else {
+                            None
+                        };
+                        let r = bsx.send(Event::TurnEnd(world, gen));
+                        if r.is_err() {
+                            break;
+                        }
+                        if let Some(c) = found {
+                            cycle = Some(c);
+                            let _ = bsx.send(Event::Cycle(c));
+                            match on_cycle {
+                                OnCycle::Continue => (),
+                                // stays open to look around and resume
+                                OnCycle::Pause => {
+                                    paused.store(true, sync::atomic::Ordering::SeqCst)
+                                }
+                                OnCycle::Exit => {
+                                    running.store(false, sync::atomic::Ordering::SeqCst);
+                                    break;
+                                }
+                            }
+                        }
+                    }
+                    if let Some(s) = stats.as_mut().filter(|_| written.is_ok()) {
+                        written = s.flush();
+                    }
+                    (cycle, simulation, written)
+                })
+        });
+
+        run_event_loop(
+            running,
+            paused,
+            tx,
+            args.backend,
+            args.charset,
+            args.background,
+            hashlife.then_some(step),
+            rule,
+            undo_bytes,
+        )?;
+        let (cycle, simulation, written) = sim
+            .join()
+            .map_err(|_| anyhow!("simulation thread panicked"))?;
+        written.context("failed to write stats")?;
+        Ok((cycle, simulation))
+    })
+}
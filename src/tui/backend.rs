@@ -0,0 +1,63 @@
+use anyhow::{bail, Result};
+use gol_rs::bgrid::Color;
+
+use crate::args::Backend;
+
+#[cfg(feature = "crossterm")]
+mod cross;
+#[cfg(feature = "curses")]
+mod curses;
+
+/// Input from the terminal, with control characters already told apart
+/// from typed ones
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Input {
+    Char(char),
+    Enter,
+    Backspace,
+    Escape,
+    Up,
+    Down,
+    Left,
+    Right,
+    /// Interrupt, since raw mode keeps Ctrl-C from raising a signal
+    Quit,
+    /// The terminal changed size
+    Resize,
+    /// Mouse button pressed over column `x` of row `y`
+    Click {
+        x: i32,
+        y: i32,
+    },
+    /// Mouse wheel turned, `down` towards the user
+    Scroll {
+        down: bool,
+    },
+}
+
+/// Screen the interface draws on, so it can run on curses or crossterm
+pub trait Terminal {
+    /// Columns and rows
+    fn size(&self) -> (i32, i32);
+    fn clear(&mut self);
+    /// Write `text` from column `x` of row `y`, anything off the screen is
+    /// dropped
+    fn put(&mut self, x: i32, y: i32, text: &str, color: Color);
+    /// Show what was drawn since the last refresh
+    fn refresh(&mut self) -> Result<()>;
+    /// Next input if one is waiting
+    fn poll(&mut self) -> Result<Option<Input>>;
+}
+
+/// Take over the terminal with `backend`, it's given back when the
+/// returned value is dropped
+pub fn open(backend: Backend) -> Result<Box<dyn Terminal>> {
+    match backend {
+        #[cfg(feature = "curses")]
+        Backend::Curses => Ok(Box::new(curses::Curses::new())),
+        #[cfg(feature = "crossterm")]
+        Backend::Crossterm => Ok(Box::new(cross::Crossterm::new()?)),
+        #[allow(unreachable_patterns)]
+        b => bail!("built without the {} backend", b),
+    }
+}
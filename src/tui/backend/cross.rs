@@ -0,0 +1,170 @@
+use anyhow::Result;
+use crossterm::{
+    cursor,
+    event::{
+        self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEventKind, KeyModifiers,
+        MouseButton, MouseEventKind,
+    },
+    execute, queue, style, terminal,
+};
+use gol_rs::bgrid::Color;
+use std::{
+    io::{BufWriter, Stdout, Write},
+    time::Duration,
+};
+
+use super::{Input, Terminal};
+
+/// Color drawn for each of the palette's colors
+fn rgb(color: Color) -> style::Color {
+    let (r, g, b) = match color {
+        Color::White => (229, 229, 229),
+        Color::Red => (235, 80, 70),
+        Color::Green => (90, 200, 120),
+        Color::Yellow => (240, 200, 60),
+        Color::Blue => (90, 150, 255),
+        Color::Magenta => (205, 110, 225),
+        Color::Cyan => (80, 210, 220),
+    };
+    style::Color::Rgb { r, g, b }
+}
+
+/// Terminal driven directly with escape codes through crossterm. Drawing
+/// goes to a buffer and only the cells which changed since the last
+/// refresh are written out
+pub struct Crossterm {
+    out: BufWriter<Stdout>,
+    width: u16,
+    height: u16,
+    cells: Vec<(char, Color)>,
+    /// What the terminal shows, `None` where it isn't known
+    shown: Vec<Option<(char, Color)>>,
+}
+
+impl Crossterm {
+    pub fn new() -> Result<Self> {
+        let mut out = BufWriter::new(std::io::stdout());
+        terminal::enable_raw_mode()?;
+        execute!(
+            out,
+            terminal::EnterAlternateScreen,
+            cursor::Hide,
+            EnableMouseCapture
+        )?;
+        let (width, height) = terminal::size()?;
+        let mut term = Self {
+            out,
+            width: 0,
+            height: 0,
+            cells: Vec::new(),
+            shown: Vec::new(),
+        };
+        term.resize(width, height)?;
+        Ok(term)
+    }
+    fn resize(&mut self, width: u16, height: u16) -> Result<()> {
+        self.width = width;
+        self.height = height;
+        let len = width as usize * height as usize;
+        self.cells = vec![(' ', Color::White); len];
+        self.shown = vec![None; len];
+        queue!(self.out, terminal::Clear(terminal::ClearType::All))?;
+        Ok(())
+    }
+}
+
+impl Drop for Crossterm {
+    fn drop(&mut self) {
+        let _ = execute!(
+            self.out,
+            style::ResetColor,
+            DisableMouseCapture,
+            cursor::Show,
+            terminal::LeaveAlternateScreen
+        );
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+impl Terminal for Crossterm {
+    fn size(&self) -> (i32, i32) {
+        (self.width as i32, self.height as i32)
+    }
+    fn clear(&mut self) {
+        self.cells.fill((' ', Color::White));
+    }
+    fn put(&mut self, x: i32, y: i32, text: &str, color: Color) {
+        if y < 0 || y >= self.height as i32 {
+            return;
+        }
+        for (i, c) in text.chars().enumerate() {
+            let x = x + i as i32;
+            if (0..self.width as i32).contains(&x) {
+                self.cells[y as usize * self.width as usize + x as usize] = (c, color);
+            }
+        }
+    }
+    fn refresh(&mut self) -> Result<()> {
+        let mut color = None;
+        // where the cursor is left after the last print, so runs of changed
+        // cells don't each need a move
+        let mut cursor = None;
+        for (i, cell) in self.cells.iter().enumerate() {
+            if self.shown[i] == Some(*cell) {
+                continue;
+            }
+            let (x, y) = (
+                (i % self.width as usize) as u16,
+                (i / self.width as usize) as u16,
+            );
+            if cursor != Some((x, y)) {
+                queue!(self.out, cursor::MoveTo(x, y))?;
+            }
+            if color != Some(cell.1) {
+                queue!(self.out, style::SetForegroundColor(rgb(cell.1)))?;
+                color = Some(cell.1);
+            }
+            queue!(self.out, style::Print(cell.0))?;
+            cursor = Some((x + 1, y));
+            self.shown[i] = Some(*cell);
+        }
+        self.out.flush()?;
+        Ok(())
+    }
+    fn poll(&mut self) -> Result<Option<Input>> {
+        if !event::poll(Duration::ZERO)? {
+            return Ok(None);
+        }
+        Ok(match event::read()? {
+            Event::Key(k) if k.kind == KeyEventKind::Release => None,
+            Event::Key(k) => match k.code {
+                KeyCode::Char('c') if k.modifiers.contains(KeyModifiers::CONTROL) => {
+                    Some(Input::Quit)
+                }
+                KeyCode::Char(c) => Some(Input::Char(c)),
+                KeyCode::Enter => Some(Input::Enter),
+                KeyCode::Backspace => Some(Input::Backspace),
+                KeyCode::Esc => Some(Input::Escape),
+                KeyCode::Up => Some(Input::Up),
+                KeyCode::Down => Some(Input::Down),
+                KeyCode::Left => Some(Input::Left),
+                KeyCode::Right => Some(Input::Right),
+                _ => None,
+            },
+            Event::Resize(width, height) => {
+                self.resize(width, height)?;
+                Some(Input::Resize)
+            }
+            Event::Mouse(m) => match m.kind {
+                MouseEventKind::Down(MouseButton::Left) => Some(Input::Click {
+                    x: m.column as i32,
+                    y: m.row as i32,
+                }),
+                MouseEventKind::ScrollDown => Some(Input::Scroll { down: true }),
+                MouseEventKind::ScrollUp => Some(Input::Scroll { down: false }),
+                _ => None,
+            },
+            _ => None,
+        })
+    }
+}
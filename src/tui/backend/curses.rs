@@ -0,0 +1,99 @@
+use anyhow::Result;
+use gol_rs::bgrid::Color;
+use pancurses::{
+    curs_set, endwin, getmouse, init_pair, mousemask, noecho, start_color, Window,
+    ALL_MOUSE_EVENTS, BUTTON1_CLICKED, BUTTON1_PRESSED, BUTTON4_PRESSED, BUTTON5_PRESSED,
+};
+
+use super::{Input, Terminal};
+
+/// Color pairs set up for each color, pair 0 is the terminal's default
+const PAIRS: [(Color, i16, i16); 7] = [
+    (Color::White, 0, pancurses::COLOR_WHITE),
+    (Color::Green, 3, pancurses::COLOR_GREEN),
+    (Color::Red, 4, pancurses::COLOR_RED),
+    (Color::Yellow, 5, pancurses::COLOR_YELLOW),
+    (Color::Blue, 6, pancurses::COLOR_BLUE),
+    (Color::Magenta, 7, pancurses::COLOR_MAGENTA),
+    (Color::Cyan, 8, pancurses::COLOR_CYAN),
+];
+
+/// Terminal through the system's curses library
+pub struct Curses {
+    win: Window,
+}
+
+impl Curses {
+    pub fn new() -> Self {
+        let win = pancurses::initscr();
+        win.keypad(true);
+        win.nodelay(true);
+        curs_set(0);
+        noecho();
+        mousemask(ALL_MOUSE_EVENTS, None);
+        start_color();
+        for (_, pair, fg) in PAIRS {
+            init_pair(pair, fg, pancurses::COLOR_BLACK);
+        }
+        win.clear();
+        win.refresh();
+        Self { win }
+    }
+}
+
+impl Drop for Curses {
+    fn drop(&mut self) {
+        endwin();
+    }
+}
+
+impl Terminal for Curses {
+    fn size(&self) -> (i32, i32) {
+        (self.win.get_max_x(), self.win.get_max_y())
+    }
+    fn clear(&mut self) {
+        self.win.clear();
+    }
+    fn put(&mut self, x: i32, y: i32, text: &str, color: Color) {
+        let pair = PAIRS
+            .iter()
+            .find(|(c, _, _)| *c == color)
+            .map_or(0, |p| p.1);
+        self.win.color_set(pair);
+        // curses reports writing the bottom right cell as an error since
+        // the cursor can't move past it, nothing is lost either way
+        self.win.mvaddstr(y, x, text);
+    }
+    fn refresh(&mut self) -> Result<()> {
+        self.win.refresh();
+        Ok(())
+    }
+    fn poll(&mut self) -> Result<Option<Input>> {
+        use pancurses::Input as I;
+        Ok(self.win.getch().and_then(|i| match i {
+            I::Character('\n' | '\r') | I::KeyEnter => Some(Input::Enter),
+            I::Character('\u{1b}') => Some(Input::Escape),
+            I::Character('\u{7f}' | '\u{8}') | I::KeyBackspace => Some(Input::Backspace),
+            I::Character(c) => Some(Input::Char(c)),
+            I::KeyUp => Some(Input::Up),
+            I::KeyDown => Some(Input::Down),
+            I::KeyLeft => Some(Input::Left),
+            I::KeyRight => Some(Input::Right),
+            I::KeyResize => Some(Input::Resize),
+            I::KeyEIC => Some(Input::Quit),
+            I::KeyMouse => {
+                let ev = getmouse().ok()?;
+                if ev.bstate & (BUTTON1_PRESSED | BUTTON1_CLICKED) != 0 {
+                    Some(Input::Click { x: ev.x, y: ev.y })
+                } else if ev.bstate & (BUTTON4_PRESSED | BUTTON5_PRESSED) != 0 {
+                    Some(Input::Scroll {
+                        down: ev.bstate & BUTTON5_PRESSED != 0,
+                    })
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        }))
+    }
+}
@@ -0,0 +1,112 @@
+use crate::{
+    bgrid::{Color, Paint, Palette},
+    gol::CellState,
+    neighborhood::Neighborhood,
+    rule::Rule,
+};
+
+/// Cell of a Wireworld circuit
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Wire {
+    #[default]
+    Empty,
+    /// Front of an electron, these are the cells counted as alive
+    Head,
+    /// Back of an electron
+    Tail,
+    Conductor,
+}
+
+impl CellState for Wire {
+    fn code(self) -> u8 {
+        self as u8
+    }
+    fn from_code(code: u8) -> Self {
+        match code {
+            1 => Wire::Head,
+            2 => Wire::Tail,
+            3 => Wire::Conductor,
+            _ => Wire::Empty,
+        }
+    }
+}
+
+/// Brian Silverman's Wireworld: electrons made of a head and a tail run
+/// along conductors, a conductor turns into a head when one or two of its
+/// neighbors are heads
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Wireworld;
+
+impl Wireworld {
+    pub const STATES: u8 = 4;
+}
+
+impl Rule for Wireworld {
+    type State = Wire;
+
+    fn neighborhood(&self) -> Neighborhood {
+        Neighborhood::Moore
+    }
+    fn next(&self, cell: Wire, neighbors: &[Wire; 8]) -> Wire {
+        match cell {
+            Wire::Empty => Wire::Empty,
+            Wire::Head => Wire::Tail,
+            Wire::Tail => Wire::Conductor,
+            Wire::Conductor => match neighbors.iter().filter(|n| **n == Wire::Head).count() {
+                1 | 2 => Wire::Head,
+                _ => Wire::Conductor,
+            },
+        }
+    }
+    fn palette(&self) -> Palette {
+        let paint = |glyph, color| Paint { glyph, color };
+        Palette::new(vec![
+            paint(' ', Color::White),
+            paint('@', Color::Blue),
+            paint('~', Color::Red),
+            paint('#', Color::Yellow),
+        ])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        gol::{Board, Point},
+        rule::step,
+    };
+
+    fn wire(rows: &[&str]) -> Board<Wire> {
+        let cell = |c| match c {
+            '@' => Wire::Head,
+            '~' => Wire::Tail,
+            '#' => Wire::Conductor,
+            _ => Wire::Empty,
+        };
+        let cells = rows.iter().flat_map(|r| r.chars().map(cell)).collect();
+        Board::from_cells(rows[0].len() as u32, cells)
+    }
+
+    #[test]
+    fn test_electron_runs_along_wire() {
+        let board = wire(&[".......", ".~@###.", "......."]);
+        let next = step(&board, &Wireworld);
+        let row = |b: &Board<Wire>| {
+            (0..7)
+                .map(|x| b.cell(Point { x, y: 1 }))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(row(&next), row(&wire(&[".......", ".#~@##.", "......."])));
+        let next = step(&next, &Wireworld);
+        assert_eq!(row(&next), row(&wire(&[".......", ".##~@#.", "......."])));
+        assert_eq!(next.alive(), 1);
+    }
+    #[test]
+    fn test_crowded_conductor_stays() {
+        let board = wire(&["@@@", "@#.", "..."]);
+        let next = step(&board, &Wireworld);
+        assert_eq!(next.cell(Point { x: 1, y: 1 }), Wire::Conductor);
+        assert_eq!(Wire::from_code(Wire::Tail.code()), Wire::Tail);
+    }
+}